@@ -1,15 +1,28 @@
-use connector_agent::{pg, s3};
+use arrow::record_batch::RecordBatch;
+use connector_agent::{
+    pg, s3, writers::arrow::RecordBatchStream, CancellationToken, PartitionProgress,
+};
 use failure::Fallible;
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::{IntoPyDict, PyTuple};
 use pyo3::wrap_pyfunction;
+use pyo3::PyIterProtocol;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::task::Poll;
+use std::thread;
+use std::time::Duration;
 use tokio::runtime;
 
+/// How often signals such as Ctrl-C are checked for while waiting for a run.
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
 #[pymodule]
 fn connector_agent(_: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(read_s3))?;
     m.add_wrapped(wrap_pyfunction!(read_pg))?;
+    m.add_wrapped(wrap_pyfunction!(iter_sql))?;
+    m.add_class::<BatchIter>()?;
     m.add_wrapped(wrap_pyfunction!(test))?;
     Ok(())
 }
@@ -53,18 +66,27 @@ fn test() -> PyResult<()> {
     Ok(())
 }
 
-#[pyfunction]
+#[pyfunction(value_map = "None", columns = "None")]
 fn read_s3(
     bucket: &str,
     objects: Vec<String>,
     schema: &str,
     json_format: &str,
+    value_map: Option<&str>,
+    columns: Option<Vec<String>>,
     py: Python,
 ) -> PyResult<PyObject> {
     let ret: Fallible<Vec<(String, Vec<(isize, isize)>)>> = py.allow_threads(|| {
         let r = runtime::Runtime::new()?;
 
-        let ret = r.block_on(s3::read_s3(bucket, &objects, schema, json_format.parse()?))?;
+        let ret = r.block_on(s3::read_s3(
+            bucket,
+            &objects,
+            schema,
+            json_format.parse()?,
+            value_map,
+            columns.as_deref(),
+        ))?;
         Ok(ret
             .into_iter()
             .map(|(k, v)| {
@@ -86,23 +108,40 @@ fn read_s3(
     PyResult::Ok(ret.into_py_dict(py).to_object(py))
 }
 
-#[pyfunction]
-fn read_pg(sqls: Vec<String>, schema: &str, py: Python) -> PyResult<PyObject> {
-    let ret: Fallible<Vec<(String, Vec<(isize, isize)>)>> = py.allow_threads(|| {
-        let r = runtime::Runtime::new()?;
-        let ret = r.block_on(pg::read_pg(&sqls, schema))?;
-        Ok(ret
-            .into_iter()
-            .map(|(k, v)| {
-                (
-                    k,
-                    v.into_iter()
-                        .map(|(a, b)| (a as isize, b as isize))
-                        .collect(),
-                )
-            })
-            .collect())
-    });
+#[pyfunction(
+    schema = "None",
+    progress = "None",
+    timeout = "None",
+    query_timeout = "None",
+    concurrency = "None"
+)]
+fn read_pg(
+    sqls: Vec<String>,
+    schema: Option<String>,
+    progress: Option<PyObject>,
+    timeout: Option<f64>,
+    query_timeout: Option<f64>,
+    concurrency: Option<usize>,
+    py: Python,
+) -> PyResult<PyObject> {
+    let options = run_options(progress, timeout, query_timeout, concurrency)?;
+    let cancellation = options.cancellation.clone();
+    let ret: Fallible<Vec<(String, Vec<(isize, isize)>)>> =
+        interruptible(py, &cancellation, move || {
+            let r = runtime::Runtime::new()?;
+            let ret = r.block_on(pg::read_pg(&sqls, schema.as_deref(), options))?;
+            Ok(ret
+                .into_iter()
+                .map(|(k, v)| {
+                    (
+                        k,
+                        v.into_iter()
+                            .map(|(a, b)| (a as isize, b as isize))
+                            .collect(),
+                    )
+                })
+                .collect())
+        })?;
 
     let ret: Vec<_> = ret
         .map_err(|e| PyValueError::new_err(format!("{:?}", e)))?
@@ -111,3 +150,219 @@ fn read_pg(sqls: Vec<String>, schema: &str, py: Python) -> PyResult<PyObject> {
         .collect();
     PyResult::Ok(ret.into_py_dict(py).to_object(py))
 }
+
+#[pyfunction(
+    batch_size = "65536",
+    progress = "None",
+    timeout = "None",
+    query_timeout = "None",
+    concurrency = "None"
+)]
+fn iter_sql(
+    sqls: Vec<String>,
+    batch_size: usize,
+    progress: Option<PyObject>,
+    timeout: Option<f64>,
+    query_timeout: Option<f64>,
+    concurrency: Option<usize>,
+    py: Python,
+) -> PyResult<BatchIter> {
+    if batch_size == 0 {
+        return Err(PyValueError::new_err("batch_size must be positive"));
+    }
+    let options = run_options(progress, timeout, query_timeout, concurrency)?;
+    let cancellation = options.cancellation.clone();
+    let stream = interruptible(py, &cancellation, move || {
+        pg::iter_sql(sqls, batch_size, options)
+    })?
+    .map_err(|e| PyValueError::new_err(format!("{:?}", e)))?;
+    Ok(BatchIter {
+        stream: Some(stream),
+    })
+}
+
+/// Iterator over the pyarrow `RecordBatch`es of `iter_sql`. Leaving the loop early, calling
+/// `close` or interrupting it with Ctrl-C drops the stream and cancels the queries that are
+/// still running.
+#[pyclass]
+struct BatchIter {
+    stream: Option<RecordBatchStream>,
+}
+
+#[pymethods]
+impl BatchIter {
+    fn close(&mut self, py: Python) {
+        close_stream(py, self.stream.take());
+    }
+}
+
+impl Drop for BatchIter {
+    /// Leaving a loop early or losing the last reference to the iterator drops it with the GIL
+    /// held, which the partitions may be waiting for to report progress.
+    fn drop(&mut self) {
+        let stream = self.stream.take();
+        Python::with_gil(|py| close_stream(py, stream));
+    }
+}
+
+/// Drop `stream` without holding the GIL, since the dispatcher it waits for may need it to
+/// report progress.
+fn close_stream(py: Python, stream: Option<RecordBatchStream>) {
+    if let Some(stream) = stream {
+        py.allow_threads(move || drop(stream));
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for BatchIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let stream = match slf.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+
+        loop {
+            match py.allow_threads(|| stream.next_timeout(SIGNAL_CHECK_INTERVAL)) {
+                Poll::Pending => {
+                    if let Err(e) = py.check_signals() {
+                        close_stream(py, slf.stream.take());
+                        return Err(e);
+                    }
+                }
+                Poll::Ready(Some(Ok(batch))) => return to_pyarrow(py, &batch).map(Some),
+                Poll::Ready(Some(Err(e))) => {
+                    close_stream(py, slf.stream.take());
+                    return Err(PyValueError::new_err(format!("{:?}", e)));
+                }
+                Poll::Ready(None) => {
+                    close_stream(py, slf.stream.take());
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Move `batch` into a pyarrow `RecordBatch` through the arrow C data interface.
+fn to_pyarrow(py: Python, batch: &RecordBatch) -> PyResult<PyObject> {
+    let pa = py.import("pyarrow")?;
+    let mut arrays = vec![];
+    let mut names = vec![];
+    for (i, f) in batch.schema().fields().iter().enumerate() {
+        let (array, schema) = batch
+            .column(i)
+            .to_raw()
+            .map_err(|e| PyValueError::new_err(format!("{:?}", e)))?;
+        arrays.push(
+            pa.getattr("Array")?
+                .call_method1("_import_from_c", (array as usize, schema as usize))?,
+        );
+        names.push(f.name().clone());
+    }
+    let batch = pa
+        .getattr("RecordBatch")?
+        .call_method1("from_arrays", (arrays, names))?;
+    Ok(batch.to_object(py))
+}
+
+/// Wrap a Python callable as a progress callback. It is called with the partition, the query it
+/// is a part of, the name of the phase, the rows written, the rows expected (or `None`) and the
+/// bytes read.
+fn progress_fn(callback: PyObject) -> pg::ProgressFn {
+    Box::new(move |p: &PartitionProgress| {
+        Python::with_gil(|py| {
+            let args = (
+                p.partition,
+                p.query,
+                p.phase.name(),
+                p.rows,
+                p.total,
+                p.bytes,
+            );
+            if let Err(e) = callback.call1(py, args) {
+                e.print(py);
+            }
+        })
+    })
+}
+
+fn run_options(
+    progress: Option<PyObject>,
+    timeout: Option<f64>,
+    query_timeout: Option<f64>,
+    concurrency: Option<usize>,
+) -> PyResult<pg::RunOptions> {
+    if concurrency == Some(0) {
+        return Err(PyValueError::new_err("concurrency must be positive"));
+    }
+    Ok(pg::RunOptions {
+        progress: progress.map(progress_fn),
+        timeout: timeout.map(|t| duration("timeout", t)).transpose()?,
+        query_timeout: query_timeout
+            .map(|t| duration("query_timeout", t))
+            .transpose()?,
+        max_concurrency: concurrency,
+        cancellation: CancellationToken::new(),
+    })
+}
+
+/// The duration of `secs` seconds, given as the argument `name`. `Duration::from_secs_f64`
+/// panics on negative, NaN and too large seconds, which are raised as `ValueError`s instead.
+fn duration(name: &str, secs: f64) -> PyResult<Duration> {
+    // infinity and NaN fail these as well
+    if !(secs >= 0.0 && secs < u64::MAX as f64) {
+        return Err(PyValueError::new_err(format!(
+            "{} must be a non-negative, finite number of seconds, not {}",
+            name, secs
+        )));
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// Run `f` in a background thread with the GIL released, checking for signals such as Ctrl-C
+/// meanwhile. Python cannot handle them while the GIL is released, so without this Ctrl-C would
+/// be ignored until the run ends. If a signal handler raises, `cancellation` is cancelled and the
+/// exception returned once the run has stopped.
+fn interruptible<T, F>(py: Python, cancellation: &CancellationToken, f: F) -> PyResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, mut receiver) = channel();
+    let handle = thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+
+    loop {
+        // the receiver is moved in and out since `allow_threads` needs a `Send` closure
+        let (ret, r) = py.allow_threads(move || {
+            let ret = receiver.recv_timeout(SIGNAL_CHECK_INTERVAL);
+            (ret, receiver)
+        });
+        receiver = r;
+
+        match ret {
+            Ok(ret) => return Ok(ret),
+            Err(RecvTimeoutError::Timeout) => {
+                if let Err(e) = py.check_signals() {
+                    cancellation.cancel();
+                    // wait for the run to see the cancel, so that it does not outlive the call,
+                    // and drop its result without the GIL too
+                    py.allow_threads(move || {
+                        let _ = handle.join();
+                        drop(receiver);
+                    });
+                    return Err(e);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(PyRuntimeError::new_err("the run panicked"))
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use connector_agent::{
+    data_sources::{csv::CSVSourceBuilder, mmap::MmapCSVSourceBuilder},
+    writers::mixed::MemoryWriter,
+    DataType, Dispatcher,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::Rng;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+const NFILES: usize = 4;
+const NROWS: usize = 250000;
+
+/// Write `NFILES` csv files of `NROWS` rows, each holding an integer, a float and a short
+/// string, into the temporary directory.
+fn generate() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..NFILES)
+        .map(|i| {
+            let path: PathBuf = std::env::temp_dir().join(format!("bench_csv_{}.csv", i));
+            let mut file = BufWriter::new(File::create(&path).unwrap());
+            for _ in 0..NROWS {
+                let (n, f): (u64, f64) = (rng.gen(), rng.gen());
+                writeln!(file, "{},{},name {}", n, f, n % 1000).unwrap();
+            }
+            path.to_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+fn bench_csv(c: &mut Criterion) {
+    let files = generate();
+    let schema = vec![DataType::U64, DataType::F64, DataType::String];
+
+    let mut group = c.benchmark_group("csv");
+    group.bench_function("csv", |b| {
+        b.iter(|| {
+            let builder = CSVSourceBuilder::new();
+            let dispatcher =
+                Dispatcher::new(builder, MemoryWriter::new(), schema.clone(), files.clone());
+            dispatcher.run_checked().expect("run dispatcher");
+        })
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            let builder = MmapCSVSourceBuilder::new();
+            let dispatcher =
+                Dispatcher::new(builder, MemoryWriter::new(), schema.clone(), files.clone());
+            dispatcher.run_checked().expect("run dispatcher");
+        })
+    });
+    group.finish();
+
+    for path in &files {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+criterion_group!(
+    name=benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_csv
+);
+criterion_main!(benches);
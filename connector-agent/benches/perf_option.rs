@@ -236,3 +236,30 @@ impl Produce<String> for U64TestSource {
         throw!(anyhow!("Only Option<u64> is supported"));
     }
 }
+
+macro_rules! impl_produce_unsupported {
+    ($($t:ty),+) => {
+        $(
+            impl Produce<$t> for U64TestSource {
+                fn produce(&mut self) -> Result<$t> {
+                    throw!(anyhow!("Only Option<u64> is supported"));
+                }
+            }
+        )+
+    };
+}
+
+impl_produce_unsupported!(
+    i64,
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<chrono::NaiveDate>,
+    Option<chrono::NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
@@ -53,6 +53,8 @@ async fn main() -> Fallible<()> {
         ],
         &to_string(&schema.to_json())?,
         "JsonL".parse()?,
+        None,
+        None,
     )
     .await?;
     Ok(())
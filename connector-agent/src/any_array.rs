@@ -2,7 +2,7 @@ use ndarray::{Array, ArrayView, ArrayViewMut, Axis, Dimension, Ix};
 use std::any::{Any, TypeId};
 use std::mem::transmute;
 
-trait AnyArrayObject<D> {
+trait AnyArrayObject<D>: Send {
     fn view_mut<'a>(&'a mut self) -> Box<dyn ArrayViewMutObject<'a, D> + 'a>;
     fn view<'a>(&'a self) -> Box<dyn ArrayViewObject<'a, D> + 'a>;
     fn as_any(&self) -> &dyn Any;
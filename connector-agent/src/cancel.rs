@@ -0,0 +1,227 @@
+use crate::errors::{ConnectorAgentError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Callback = Box<dyn Fn() + Send + Sync>;
+
+/// A token to cancel a dispatcher run from another thread. The dispatcher checks it between
+/// batches of rows, and sources can register callbacks with `on_cancel` to interrupt the
+/// blocking calls they are in, e.g. by sending a cancel request to the database.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    callbacks: Mutex<Callbacks>,
+}
+
+/// The callbacks registered and not unregistered yet, by the id of their guard.
+#[derive(Default)]
+struct Callbacks {
+    next_id: u64,
+    registered: HashMap<u64, Callback>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token and run the callbacks currently registered. Cancelling again does nothing.
+    /// The callbacks run outside of the lock, since they may block, e.g. on a cancel request, so
+    /// a callback can still be running while its guard is dropped.
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let callbacks = std::mem::take(&mut self.inner.callbacks.lock().unwrap().registered);
+        for callback in callbacks.values() {
+            callback();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail with `ConnectorAgentError::Cancelled` if the token is cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(ConnectorAgentError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Run `callback` if the token is cancelled before the returned guard is dropped, or right
+    /// away if it is cancelled already.
+    pub fn on_cancel<F>(&self, callback: F) -> CancelGuard
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let mut callbacks = self.inner.callbacks.lock().unwrap();
+        // `cancel` sets the flag before taking the lock, so the callback runs exactly once
+        if self.is_cancelled() {
+            drop(callbacks);
+            callback();
+            return CancelGuard {
+                token: self.clone(),
+                id: None,
+            };
+        }
+        let id = callbacks.next_id;
+        callbacks.next_id += 1;
+        callbacks.registered.insert(id, Box::new(callback));
+        CancelGuard {
+            token: self.clone(),
+            id: Some(id),
+        }
+    }
+}
+
+/// Unregisters the callback passed to `CancellationToken::on_cancel` when dropped.
+pub struct CancelGuard {
+    token: CancellationToken,
+    id: Option<u64>,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut callbacks = self.token.inner.callbacks.lock().unwrap();
+            callbacks.registered.remove(&id);
+        }
+    }
+}
+
+/// Calls functions from a single background thread once their timeouts elapse, so that timing
+/// many calls, e.g. every fetch from a cursor, does not start a thread for each of them. The
+/// thread exits once the `Timers` and the `Timer`s it started are all dropped.
+pub(crate) struct Timers {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+struct State {
+    next_id: u64,
+    // the functions not called yet, by their deadline
+    pending: BTreeMap<(Instant, u64), Box<dyn FnOnce() + Send>>,
+    // the `Timers` and `Timer`s not dropped yet
+    handles: usize,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                next_id: 0,
+                pending: BTreeMap::new(),
+                handles: 1,
+            }),
+            wake: Condvar::new(),
+        });
+        let shared_ = Arc::clone(&shared);
+        thread::spawn(move || run_timers(&shared_));
+        Timers { shared }
+    }
+
+    /// Call `f` once `timeout` elapses, unless the returned timer is dropped first.
+    pub fn start<F>(&self, timeout: Duration, f: F) -> Timer
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let fired = Arc::new(AtomicBool::new(false));
+        let fired_ = Arc::clone(&fired);
+        let mut state = self.shared.state.lock().unwrap();
+        let key = (Instant::now() + timeout, state.next_id);
+        state.next_id += 1;
+        state.handles += 1;
+        state.pending.insert(
+            key,
+            Box::new(move || {
+                fired_.store(true, Ordering::SeqCst);
+                f();
+            }),
+        );
+        self.shared.wake.notify_one();
+        Timer {
+            timeout,
+            fired,
+            key,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for Timers {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().handles -= 1;
+        self.shared.wake.notify_one();
+    }
+}
+
+/// Call the functions of `shared` as their deadlines pass, outside of the lock.
+fn run_timers(shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        match state.pending.keys().next().copied() {
+            Some(key) if key.0 <= now => {
+                let f = state.pending.remove(&key).unwrap();
+                drop(state);
+                f();
+                state = shared.state.lock().unwrap();
+            }
+            Some((deadline, _)) => {
+                state = shared.wake.wait_timeout(state, deadline - now).unwrap().0;
+            }
+            None if state.handles == 0 => return,
+            None => state = shared.wake.wait(state).unwrap(),
+        }
+    }
+}
+
+/// Calls a function once `timeout` elapses, unless it is dropped first.
+pub(crate) struct Timer {
+    timeout: Duration,
+    fired: Arc<AtomicBool>,
+    key: (Instant, u64),
+    shared: Arc<Shared>,
+}
+
+impl Timer {
+    /// Call `f` from a thread of its own once `timeout` elapses.
+    pub fn start<F>(timeout: Duration, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Timers::new().start(timeout, f)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Whether the timeout elapsed and the function was called.
+    pub fn fired(&self) -> bool {
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.pending.remove(&self.key);
+        state.handles -= 1;
+        self.shared.wake.notify_one();
+    }
+}
@@ -0,0 +1,87 @@
+use crate::errors::ConnectorAgentError;
+use fehler::throws;
+use flate2::bufread::MultiGzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+
+/// A file decoded while it is read.
+pub type Decoded = BufReader<Box<dyn Read + Send>>;
+
+/// The compression of the files read by text-based sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Gzip, including files of several concatenated gzip members.
+    Gzip,
+    Zstd,
+    Bzip2,
+    /// Tell the compression from the extension of the file, or else from its first bytes.
+    Auto,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Auto
+    }
+}
+
+impl Compression {
+    /// The compression of the file at `path` starting with `head`, by its extension or, failing
+    /// that, by the magic bytes of the formats.
+    pub fn detect(path: &str, head: &[u8]) -> Self {
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        match extension.as_str() {
+            "gz" | "gzip" => return Compression::Gzip,
+            "zst" | "zstd" => return Compression::Zstd,
+            "bz2" | "bzip2" => return Compression::Bzip2,
+            _ => {}
+        }
+
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        // `BZh`, the block size and the magic number of the first block
+        } else if head.len() >= 10
+            && head.starts_with(b"BZh")
+            && (b'1'..=b'9').contains(&head[3])
+            && head[4..10] == [0x31, 0x41, 0x59, 0x26, 0x53, 0x59]
+        {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Open the file at `path` and decode it while it is read, returning the compression it
+    /// turned out to have if it is detected.
+    #[throws(ConnectorAgentError)]
+    pub fn open(self, path: &str) -> (Decoded, Compression) {
+        let file = File::open(path).map_err(anyhow::Error::from)?;
+        self.decode(path, Box::new(file))?
+    }
+
+    /// Decode `input` while it is read, where `name` is the path or another name of the input
+    /// that detection can tell the compression from.
+    #[throws(ConnectorAgentError)]
+    pub fn decode(self, name: &str, input: Box<dyn Read + Send>) -> (Decoded, Compression) {
+        let mut input = BufReader::new(input);
+        let compression = match self {
+            Compression::Auto => {
+                let head = input.fill_buf().map_err(anyhow::Error::from)?;
+                Compression::detect(name, head)
+            }
+            compression => compression,
+        };
+
+        let decoded: Box<dyn Read + Send> = match compression {
+            Compression::None | Compression::Auto => Box::new(input),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+            Compression::Zstd => {
+                Box::new(zstd::Decoder::with_buffer(input).map_err(anyhow::Error::from)?)
+            }
+            Compression::Bzip2 => Box::new(bzip2::bufread::BzDecoder::new(input)),
+        };
+        (BufReader::new(decoded), compression)
+    }
+}
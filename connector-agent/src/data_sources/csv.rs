@@ -1,15 +1,621 @@
-use super::{DataSource, Produce, SourceBuilder};
+use super::compression::{Compression, Decoded};
+use super::files;
+use super::reader::Opener;
+use super::text::{FromText, TextFields, TextOptions, TypeInference};
+use super::{DataSource, SourceBuilder};
 use crate::data_order::DataOrder;
 use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
+use crate::split::{SplitQuery, CLAIM_PARTS};
 use crate::types::DataType;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime};
 use fehler::{throw, throws};
+use log::warn;
+use std::borrow::Cow;
 use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
-pub struct CSVSourceBuilder {}
+/// The dialect of the csv files read. The defaults match the csv crate: no header row,
+/// comma-delimited fields quoted with `"`, and records of equal length.
+#[derive(Clone, Debug)]
+pub struct CSVOptions {
+    /// The first record, after the skipped rows, holds the names of the columns.
+    pub has_headers: bool,
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Read quotes as plain characters if false.
+    pub quoting: bool,
+    /// The character escaping quotes inside quoted fields. If `None`, quotes are escaped by
+    /// doubling them.
+    pub escape: Option<u8>,
+    /// Skip the records starting with this character.
+    pub comment: Option<u8>,
+    /// Lines skipped at the start of every file, before the header row.
+    pub skip_rows: usize,
+    /// Trim the whitespace around the fields and the headers.
+    pub trim: bool,
+    /// Accept records with more or fewer fields than the first one. Missing fields are read as
+    /// empty, and extra fields are dropped.
+    pub flexible: bool,
+    /// What to do with the values that do not parse as the type of their column.
+    pub on_error: OnError,
+    /// The spellings of nulls and booleans.
+    pub text: TextOptions,
+    pub compression: Compression,
+    /// Add a last column of this name holding the path of the file each row is read from.
+    pub filename_column: Option<String>,
+}
+
+/// What a `CSVSource` does with a value that does not parse as the type of its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnError {
+    /// Fail with `ConnectorAgentError::CSVParseError`.
+    Fail,
+    /// Read the value as null, failing as above if the column is not nullable.
+    Null,
+    /// Read the default of the type of the column: zero, false, an empty string, or null.
+    Default,
+    /// Drop the whole row, logging it and appending it to the reject file if one is given. The
+    /// rows are checked as they are read, against the schema given to the source.
+    Skip,
+}
+
+impl Default for OnError {
+    fn default() -> Self {
+        OnError::Fail
+    }
+}
+
+impl Default for CSVOptions {
+    fn default() -> Self {
+        CSVOptions {
+            has_headers: false,
+            delimiter: b',',
+            quote: b'"',
+            quoting: true,
+            escape: None,
+            comment: None,
+            skip_rows: 0,
+            trim: false,
+            flexible: false,
+            on_error: OnError::default(),
+            text: TextOptions::default(),
+            compression: Compression::default(),
+            filename_column: None,
+        }
+    }
+}
+
+impl CSVOptions {
+    /// Open the csv file, or the byte range of it, named by `query`, or the reader `opener`
+    /// returns for it if given: the input is decoded, its first `skip_rows` lines skipped and
+    /// its header read.
+    #[throws(ConnectorAgentError)]
+    fn open(&self, query: &str, opener: Option<&Opener>) -> Records {
+        let (path, range, (mut file, compression)) = match opener {
+            Some(open) => (query, None, self.compression.decode(query, open(query)?)?),
+            None => {
+                let (path, range) = parse_query(query)?;
+                (path, range, self.compression.open(path)?)
+            }
+        };
+        let mut offset = 0;
+        let mut line = vec![];
+        for _ in 0..self.skip_rows {
+            line.clear();
+            match file
+                .read_until(b'\n', &mut line)
+                .map_err(anyhow::Error::from)?
+            {
+                0 => break,
+                n => offset += n as u64,
+            }
+        }
+
+        let mut reader = self.reader(self.has_headers, file);
+        let headers = if self.has_headers {
+            Some(reader.headers()?.iter().map(String::from).collect())
+        } else {
+            None
+        };
+        let mut records = Records {
+            reader,
+            compression,
+            offset,
+            end: range.as_ref().map(|range| range.end),
+            headers,
+        };
+
+        // ranges other than the first start right at a record, after the header
+        if let Some(range) = range {
+            if compression != Compression::None {
+                throw!(anyhow!(
+                    "cannot read a byte range of the compressed file {}",
+                    path
+                ));
+            }
+            if range.start > records.position() {
+                let mut file = File::open(path).map_err(anyhow::Error::from)?;
+                file.seek(SeekFrom::Start(range.start))
+                    .map_err(anyhow::Error::from)?;
+                let file: Box<dyn Read + Send> = Box::new(file);
+                records.reader = self.reader(false, BufReader::new(file));
+                records.offset = range.start;
+            }
+        }
+        records
+    }
+
+    fn reader(&self, has_headers: bool, file: Decoded) -> csv::Reader<Decoded> {
+        csv::ReaderBuilder::new()
+            .has_headers(has_headers)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quoting(self.quoting)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .comment(self.comment)
+            .trim(if self.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .flexible(self.flexible)
+            .from_reader(file)
+    }
+}
+
+/// Split a query into the path of the file and the byte range of it to read, if any.
+fn parse_query(query: &str) -> Result<(&str, Option<Range<u64>>)> {
+    let i = match query.rfind(RANGE_MARK) {
+        Some(i) => i,
+        None => return Ok((query, None)),
+    };
+    let range = &query[i + RANGE_MARK.len()..];
+    let parsed = range.find('-').and_then(|j| {
+        let start = range[..j].parse().ok()?;
+        let end = range[j + 1..].parse().ok()?;
+        Some(start..end)
+    });
+    match parsed {
+        Some(range) => Ok((&query[..i], Some(range))),
+        None => throw!(anyhow!("invalid byte range in {}", query)),
+    }
+}
+
+/// Separates the path of a file from a byte range of it in a query.
+const RANGE_MARK: &str = "#bytes=";
+
+/// The records of a csv file, or of those starting in the byte range of it a query names.
+struct Records {
+    reader: csv::Reader<Decoded>,
+    compression: Compression,
+    /// The position in the file the reader starts reading from.
+    offset: u64,
+    end: Option<u64>,
+    headers: Option<Vec<String>>,
+}
+
+impl Records {
+    /// Read the next record into `record`, returning false once the file or the range is
+    /// exhausted.
+    fn read(&mut self, record: &mut csv::ByteRecord) -> Result<bool> {
+        if !self.reader.read_byte_record(record)? {
+            return Ok(false);
+        }
+        Ok(match self.end {
+            Some(end) => self.start_of(record) < end,
+            None => true,
+        })
+    }
+
+    /// The position in the file `record` starts at.
+    fn start_of(&self, record: &csv::ByteRecord) -> u64 {
+        self.offset + record.position().map_or(0, |pos| pos.byte())
+    }
+
+    /// The position in the file of the next record.
+    fn position(&self) -> u64 {
+        self.offset + self.reader.position().byte()
+    }
+}
+
+/// Builds `CSVSource`s reading files of the same dialect, where the query of every partition is
+/// the path of a file.
+#[derive(Default)]
+pub struct CSVSourceBuilder {
+    options: CSVOptions,
+    batch_size: Option<usize>,
+    eager: bool,
+    schema: Option<Vec<DataType>>,
+    rejects: Option<Arc<RejectFile>>,
+    sample_rows: Option<usize>,
+}
 
 impl CSVSourceBuilder {
     pub fn new() -> Self {
-        CSVSourceBuilder {}
+        Self::default()
+    }
+
+    pub fn with_options(options: CSVOptions) -> Self {
+        CSVSourceBuilder {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Read the names of the columns from the first record of the files.
+    pub fn with_headers(mut self, has_headers: bool) -> Self {
+        self.options.has_headers = has_headers;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.options.delimiter = delimiter;
+        self
+    }
+
+    /// Quote fields with `quote`, or read quotes as plain characters if `None`.
+    pub fn with_quote(mut self, quote: Option<u8>) -> Self {
+        self.options.quoting = quote.is_some();
+        if let Some(quote) = quote {
+            self.options.quote = quote;
+        }
+        self
+    }
+
+    /// Escape quotes inside quoted fields with `escape` instead of doubling them.
+    pub fn with_escape(mut self, escape: u8) -> Self {
+        self.options.escape = Some(escape);
+        self
+    }
+
+    /// Skip the records starting with `comment`.
+    pub fn with_comment(mut self, comment: u8) -> Self {
+        self.options.comment = Some(comment);
+        self
+    }
+
+    /// Skip the first `nrows` lines of every file, before the header row.
+    pub fn with_skip_rows(mut self, nrows: usize) -> Self {
+        self.options.skip_rows = nrows;
+        self
+    }
+
+    /// Trim the whitespace around the fields.
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.options.trim = trim;
+        self
+    }
+
+    /// Accept records of different lengths, reading missing fields as empty.
+    pub fn with_flexible(mut self, flexible: bool) -> Self {
+        self.options.flexible = flexible;
+        self
+    }
+
+    /// Read the files `batch_size` records at a time while the rows are written, instead of
+    /// the default of 65536. The writer needs to grow, unless the rows are counted up front
+    /// with `RowCount::Exact`.
+    #[throws(ConnectorAgentError)]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Read every file whole when its query runs instead of in batches, ignoring the batch
+    /// size. Memory then grows with the size of the files.
+    pub fn with_eager(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    /// Read the fields in `values` as null in nullable columns, instead of only empty fields.
+    pub fn with_null_values(mut self, values: &[&str]) -> Self {
+        self.options.text = self.options.text.with_null_values(values);
+        self
+    }
+
+    /// Read the fields in `true_values` and `false_values` as booleans, instead of `true` and
+    /// `false`.
+    pub fn with_bool_values(mut self, true_values: &[&str], false_values: &[&str]) -> Self {
+        self.options.text = self
+            .options
+            .text
+            .with_bool_values(true_values, false_values);
+        self
+    }
+
+    /// Spell the nulls and booleans of all columns, or of single columns, as in `text`.
+    pub fn with_text(mut self, text: TextOptions) -> Self {
+        self.options.text = text;
+        self
+    }
+
+    /// Add a last column named `name` holding the path of the file each row is read from. The
+    /// schema needs a `String` or `OptString` type for it.
+    pub fn with_filename_column(mut self, name: &str) -> Self {
+        self.options.filename_column = Some(name.to_string());
+        self
+    }
+
+    /// Generate a query for every file `pattern` stands for, see `files::expand`.
+    #[throws(ConnectorAgentError)]
+    pub fn queries(&self, pattern: &str) -> Vec<String> {
+        files::expand(pattern)?
+    }
+
+    /// Generate queries reading about `size` bytes each out of the files `pattern` stands for:
+    /// small files are read together by a single query, and files larger than `size` are split
+    /// into byte ranges, see `byte_ranges`.
+    #[throws(ConnectorAgentError)]
+    pub fn grouped_queries(&self, pattern: &str, size: u64) -> Vec<String> {
+        if size == 0 {
+            throw!(ConnectorAgentError::NotPositive("size of a query"));
+        }
+        let mut queries = vec![];
+        let mut small = vec![];
+        for path in files::expand(pattern)? {
+            let len = std::fs::metadata(&path).map_err(anyhow::Error::from)?.len();
+            if len > size {
+                queries.extend(files::group_by_size(&small, size)?);
+                small.clear();
+                let n = (len + size - 1) / size;
+                queries.extend(self.byte_ranges(&path, n as usize)?);
+            } else {
+                small.push(path);
+            }
+        }
+        queries.extend(files::group_by_size(&small, size)?);
+        queries
+    }
+
+    /// Decode the files with `compression`, instead of telling it from their names or first bytes.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    /// Choose what to do with the values that do not parse as the type of their column. By
+    /// default the run fails.
+    pub fn with_on_error(mut self, on_error: OnError) -> Self {
+        self.options.on_error = on_error;
+        self
+    }
+
+    /// The types of the columns of the files, needed by `OnError::Skip` to check the rows.
+    pub fn with_schema(mut self, schema: Vec<DataType>) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Append the rows skipped by `OnError::Skip` to the csv file at `path`, after the path
+    /// and line they are read from. The file is overwritten once the first row is skipped.
+    pub fn with_reject_file(mut self, path: &str) -> Self {
+        self.rejects = Some(Arc::new(RejectFile::new(path)));
+        self
+    }
+
+    /// Infer the schema from the first `nrows` records of every file, instead of 1000.
+    #[throws(ConnectorAgentError)]
+    pub fn with_sample_rows(mut self, nrows: usize) -> Self {
+        if nrows == 0 {
+            throw!(ConnectorAgentError::NotPositive("number of sampled rows"));
+        }
+        self.sample_rows = Some(nrows);
+        self
+    }
+
+    pub fn options(&self) -> &CSVOptions {
+        &self.options
+    }
+
+    /// Infer the names and types of the columns read by `queries`, e.g. the queries of all the
+    /// partitions of a read, from the records at the start of each of their files. A column
+    /// gets the narrowest type holding the values of every file: integers widen to floats,
+    /// dates to timestamps, and values of other different types to strings. It is nullable if
+    /// any sampled field is a null value. The names are read as in `headers`.
+    #[throws(ConnectorAgentError)]
+    pub fn describe(&self, queries: &[String]) -> (Vec<String>, Vec<DataType>) {
+        let first = match queries.first() {
+            Some(query) => query,
+            None => throw!(anyhow!("no query to describe")),
+        };
+        let names = self.headers(first)?;
+
+        // sample without rejecting rows, which needs the schema being inferred
+        let options = CSVOptions {
+            on_error: OnError::Fail,
+            ..self.options.clone()
+        };
+        let sample_rows = self.sample_rows.unwrap_or(INFER_SAMPLE_ROWS);
+        let mut inferences = vec![TypeInference::default(); names.len()];
+        for file in queries.iter().flat_map(|query| files::split(query)) {
+            let mut source =
+                CSVSource::with_options(options.clone()).with_batch_size(sample_rows)?;
+            source.run_query(file)?;
+            for (inference, other) in inferences.iter_mut().zip(source.inferences()) {
+                inference.merge(&other);
+            }
+        }
+
+        let mut schema: Vec<_> = inferences.iter().map(|i| i.data_type()).collect();
+        if self.options.filename_column.is_some() {
+            *schema.last_mut().unwrap() = DataType::String;
+        }
+        (names, schema)
+    }
+
+    /// Read the names of the columns of the file at `path`, e.g. to name the columns of the
+    /// writer. Without a header row the columns are named `column_0`, `column_1`, and so on.
+    #[throws(ConnectorAgentError)]
+    pub fn headers(&self, path: &str) -> Vec<String> {
+        let first = files::split(path).next().unwrap_or(path);
+        let mut records = self.options.open(first, None)?;
+        let mut headers = match records.headers.take() {
+            Some(headers) => headers,
+            None => {
+                let mut record = csv::ByteRecord::new();
+                records.read(&mut record)?;
+                (0..record.len()).map(|i| format!("column_{}", i)).collect()
+            }
+        };
+        headers.extend(self.options.filename_column.clone());
+        headers
+    }
+}
+
+impl CSVSourceBuilder {
+    /// Generate queries reading the file at `path` in `n` byte ranges of about the same size, so
+    /// that a single file is read in parallel. The ranges are aligned to the starts of records,
+    /// which takes a scan of the file that parses the records without their values, and there
+    /// are fewer of them if the file has fewer than `n` records. Compressed files cannot be read
+    /// from the middle, so they are read whole by a single query.
+    #[throws(ConnectorAgentError)]
+    pub fn byte_ranges(&self, path: &str, n: usize) -> Vec<String> {
+        if n == 0 {
+            throw!(ConnectorAgentError::NotPositive("number of byte ranges"));
+        }
+        let mut records = self.options.open(path, None)?;
+        if records.compression != Compression::None {
+            return vec![path.to_string()];
+        }
+        let size = std::fs::metadata(path).map_err(anyhow::Error::from)?.len();
+        let start = records.position();
+        let target = |k: usize| start + (size - start) * k as u64 / n as u64;
+
+        // the first range reads the skipped rows and the header too
+        let mut bounds = vec![0];
+        let mut record = csv::ByteRecord::new();
+        let mut k = 1;
+        while k < n && records.read(&mut record)? {
+            let pos = records.start_of(&record);
+            // the first record belongs to the first range anyway
+            if pos > start && pos >= target(k) {
+                bounds.push(pos);
+                while k < n && pos >= target(k) {
+                    k += 1;
+                }
+            }
+        }
+        bounds.push(size);
+
+        bounds
+            .windows(2)
+            .map(|w| format!("{}{}{}-{}", path, RANGE_MARK, w[0], w[1]))
+            .collect()
+    }
+
+    /// A query reading the whole file at `path`, which the `Dispatcher` can split into byte
+    /// ranges while planning, see `ByteRangeQuery`.
+    #[throws(ConnectorAgentError)]
+    pub fn split_query(&self, path: &str) -> ByteRangeQuery {
+        let size = std::fs::metadata(path).map_err(anyhow::Error::from)?.len();
+        ByteRangeQuery {
+            options: self.options.clone(),
+            path: path.to_string(),
+            size,
+            range: 0..size,
+            min_len: SPLIT_MIN_BYTES,
+        }
+    }
+}
+
+/// Byte ranges are not split into parts smaller than this by default.
+const SPLIT_MIN_BYTES: u64 = 1 << 20;
+
+/// A byte range of a csv file, split by halving it at the start of the record closest after the
+/// middle, like the ranges of `CSVSourceBuilder::byte_ranges`, and claimed in chunks ending at
+/// the start of a record the same way. Finding the record takes a scan of the range up to it.
+/// Compressed files, and ranges without a record after the middle, are not split.
+#[derive(Clone, Debug)]
+pub struct ByteRangeQuery {
+    options: CSVOptions,
+    path: String,
+    size: u64,
+    range: Range<u64>,
+    min_len: u64,
+}
+
+impl ByteRangeQuery {
+    /// Do not split the range into parts of fewer than `min_len` bytes, instead of 1 MiB.
+    #[throws(ConnectorAgentError)]
+    pub fn with_min_len(mut self, min_len: u64) -> Self {
+        if min_len == 0 {
+            throw!(ConnectorAgentError::NotPositive("minimum length of a part"));
+        }
+        self.min_len = min_len;
+        self
+    }
+
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    /// The start of the first record at or after `mid` other than the first one of the range.
+    #[throws(ConnectorAgentError)]
+    fn record_after(&self, mid: u64) -> Option<u64> {
+        let mut records = self.options.open(&self.query(), None)?;
+        if records.compression != Compression::None {
+            return None;
+        }
+        let first = records.position();
+        let mut record = csv::ByteRecord::new();
+        while records.read(&mut record)? {
+            let pos = records.start_of(&record);
+            if pos > first && pos >= mid {
+                return Some(pos);
+            }
+        }
+        None
+    }
+}
+
+impl SplitQuery for ByteRangeQuery {
+    fn query(&self) -> String {
+        if self.range == (0..self.size) {
+            return self.path.clone();
+        }
+        format!(
+            "{}{}{}-{}",
+            self.path, RANGE_MARK, self.range.start, self.range.end
+        )
+    }
+
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>> {
+        let len = self.range.end - self.range.start;
+        if len < 2 * self.min_len {
+            return None;
+        }
+        // the file is read again by the source, which reports the errors
+        let mid = self.record_after(self.range.start + len / 2).ok()??;
+        let second = ByteRangeQuery {
+            range: mid..self.range.end,
+            ..self.clone()
+        };
+        self.range.end = mid;
+        Some(Box::new(second))
+    }
+
+    fn split_front(&mut self) -> Option<Box<dyn SplitQuery>> {
+        let len = self.range.end - self.range.start;
+        let front = (len / CLAIM_PARTS).max(self.min_len);
+        if len <= front {
+            return None;
+        }
+        let mid = self.record_after(self.range.start + front).ok()??;
+        let first = ByteRangeQuery {
+            range: self.range.start..mid,
+            ..self.clone()
+        };
+        self.range.start = mid;
+        Some(Box::new(first))
     }
 }
 
@@ -25,93 +631,416 @@ impl SourceBuilder for CSVSourceBuilder {
     }
 
     fn build(&mut self) -> Self::DataSource {
-        CSVSource::new()
+        let mut source = CSVSource::with_options(self.options.clone());
+        if self.eager {
+            source = source.with_eager(true);
+        } else if let Some(batch_size) = self.batch_size {
+            source.batch_size = Some(batch_size);
+        }
+        if let Some(schema) = &self.schema {
+            source = source.with_schema(schema.clone());
+        }
+        source.rejects = self.rejects.clone();
+        source
     }
 }
 
+/// Records read at a time unless a batch size is given or the files are read whole.
+pub(crate) const BATCH_ROWS: usize = 65536;
+
+/// Records sampled from the start of a file to estimate its number of rows from its size.
+const ESTIMATE_SAMPLE_ROWS: usize = 1000;
+
+/// Records sampled from the start of every file to infer the schema.
+const INFER_SAMPLE_ROWS: usize = 1000;
+
+/// Reads a csv file, record by record into reused buffers. The records are read in batches as
+/// they are produced, so that memory stays bounded however large the file is, unless the
+/// source is made eager, in which case the whole file is read in `run_query`.
 pub struct CSVSource {
-    records: Vec<csv::StringRecord>,
+    options: CSVOptions,
+    pub(crate) batch_size: Option<usize>,
+    schema: Option<Vec<DataType>>,
+    pub(crate) rejects: Option<Arc<RejectFile>>,
+    opener: Option<Opener>,
+    // the files of the query, with and without their byte ranges, and the one being read
+    parts: Vec<String>,
+    paths: Vec<String>,
+    file: usize,
+    reader: Option<Records>,
+    headers: Option<Vec<String>>,
+    // only the first `nrows` records hold rows of the current batch
+    records: Vec<csv::ByteRecord>,
+    // the file each record is read from
+    record_files: Vec<usize>,
     counter: usize,
+    nbytes: usize,
     pub nrows: usize,
     pub ncols: usize,
 }
 
 impl CSVSource {
     pub fn new() -> Self {
+        Self::with_options(CSVOptions::default())
+    }
+
+    pub fn with_options(options: CSVOptions) -> Self {
         Self {
+            options,
+            batch_size: Some(BATCH_ROWS),
+            schema: None,
+            rejects: None,
+            opener: None,
+            parts: vec![],
+            paths: vec![],
+            file: 0,
+            reader: None,
+            headers: None,
             records: Vec::new(),
+            record_files: Vec::new(),
             counter: 0,
+            nbytes: 0,
             nrows: 0,
             ncols: 0,
         }
     }
 
+    /// Read the file `batch_size` records at a time instead of `BATCH_ROWS` at a time.
+    #[throws(ConnectorAgentError)]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Read the whole file in `run_query` instead of in batches, or go back to batches of
+    /// `BATCH_ROWS`.
+    pub fn with_eager(mut self, eager: bool) -> Self {
+        self.batch_size = if eager { None } else { Some(BATCH_ROWS) };
+        self
+    }
+
+    /// The types of the columns, which `OnError::Skip` checks the rows against as they are read.
+    pub fn with_schema(mut self, schema: Vec<DataType>) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Read the readers `open` returns for the queries instead of the files they name. The
+    /// queries are then names of the readers, which may be read several times to count rows.
+    pub fn with_opener(mut self, open: Opener) -> Self {
+        self.opener = Some(open);
+        self
+    }
+
+    /// The names of the columns of the file read, if it has a header row.
+    pub fn headers(&self) -> Option<&[String]> {
+        self.headers.as_deref()
+    }
+
+    /// Infer the types of the columns from the records of the current batch, as
+    /// `CSVSourceBuilder::describe` does for a single partition.
     pub fn infer_schema(&mut self) -> Result<Vec<DataType>> {
-        unimplemented!("infer schema using self.records!");
+        let mut schema: Vec<_> = self.inferences().iter().map(|i| i.data_type()).collect();
+        if self.options.filename_column.is_some() {
+            if let Some(ty) = schema.last_mut() {
+                *ty = DataType::String;
+            }
+        }
+        Ok(schema)
+    }
+
+    /// The types of the `ncols` columns inferred from the records of the current batch.
+    fn inferences(&self) -> Vec<TypeInference> {
+        let mut inferences = vec![TypeInference::default(); self.ncols];
+        for row in 0..self.nrows {
+            for (col, inference) in inferences.iter_mut().enumerate() {
+                inference.update(&self.field((row, col)), self.options.text.column(col));
+            }
+        }
+        inferences
+    }
+
+    /// Read the next batch of records into the buffers, moving on to the next file of the query
+    /// once a file is exhausted. Under `OnError::Skip` the records with a value that does not
+    /// parse are rejected instead.
+    fn read_batch(&mut self) -> Result<usize> {
+        let skip = match self.options.on_error {
+            OnError::Skip => self.schema.as_deref(),
+            _ => None,
+        };
+        let text = &self.options.text;
+        let mut n = 0;
+        while self.batch_size.map_or(true, |size| n < size) {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None if self.file + 1 < self.parts.len() => {
+                    self.file += 1;
+                    let part = &self.parts[self.file];
+                    self.reader = Some(self.options.open(part, self.opener.as_ref())?);
+                    continue;
+                }
+                None => break,
+            };
+            if n == self.records.len() {
+                self.records.push(csv::ByteRecord::new());
+                self.record_files.push(0);
+            }
+            let record = &mut self.records[n];
+            if !reader.read(record)? {
+                self.reader = None;
+                continue;
+            }
+            if let Some((col, &ty)) = skip.and_then(|schema| invalid_field(schema, record, text)) {
+                let (path, line) = (&self.paths[self.file], line_of(record, &self.options));
+                warn!(
+                    "skipping line {} of {}: cannot parse column {} as {:?}",
+                    line, path, col, ty
+                );
+                if let Some(rejects) = &self.rejects {
+                    rejects.reject(path, line, record)?;
+                }
+                continue;
+            }
+            self.record_files[n] = self.file;
+            n += 1;
+        }
+        self.nrows = n;
+        self.counter = 0;
+        Ok(n)
+    }
+}
+
+impl TextFields for CSVSource {
+    fn options(&self) -> &CSVOptions {
+        &self.options
+    }
+
+    fn next_cell(&mut self) -> Result<(usize, usize)> {
+        if self.counter >= self.nrows * self.ncols {
+            throw!(ConnectorAgentError::OutOfBound);
+        }
+        let cell = (self.counter / self.ncols, self.counter % self.ncols);
+        self.counter += 1;
+        self.nbytes += self.field(cell).len();
+        Ok(cell)
+    }
+
+    /// The fields missing from short records of flexible files are empty.
+    fn field(&self, (row, col): (usize, usize)) -> Cow<'_, [u8]> {
+        if self.options.filename_column.is_some() && col + 1 == self.ncols {
+            return Cow::Borrowed(self.paths[self.record_files[row]].as_bytes());
+        }
+        Cow::Borrowed(self.records[row].get(col).unwrap_or(b""))
+    }
+
+    fn parse_error<T: FromText>(&self, (row, col): (usize, usize)) -> ConnectorAgentError {
+        ConnectorAgentError::CSVParseError {
+            path: self.paths[self.record_files[row]].clone(),
+            line: line_of(&self.records[row], &self.options),
+            col,
+            text: String::from_utf8_lossy(&self.field((row, col))).into_owned(),
+            ty: T::NAME,
+        }
+    }
+}
+
+/// The line of the file where `record` starts, counting from 1. In the byte ranges but the first
+/// of a file, the lines are counted from the start of the range instead.
+fn line_of(record: &csv::ByteRecord, options: &CSVOptions) -> u64 {
+    record.position().map_or(0, |pos| pos.line()) + options.skip_rows as u64
+}
+
+/// Find the first field of `record` that does not parse as the type of its column.
+fn invalid_field<'a>(
+    schema: &'a [DataType],
+    record: &csv::ByteRecord,
+    text: &TextOptions,
+) -> Option<(usize, &'a DataType)> {
+    fn parses<T: FromText>(v: &[u8], text: &TextOptions) -> bool {
+        std::str::from_utf8(v)
+            .ok()
+            .and_then(|v| T::from_text(v, text))
+            .is_some()
+    }
+
+    schema.iter().enumerate().find(|&(col, &ty)| {
+        let v = record.get(col).unwrap_or(b"");
+        let text = text.column(col);
+        let valid = match ty {
+            DataType::F64 => parses::<f64>(v, text),
+            DataType::U64 => parses::<u64>(v, text),
+            DataType::I64 => parses::<i64>(v, text),
+            DataType::Bool => parses::<bool>(v, text),
+            DataType::String => parses::<String>(v, text),
+            DataType::OptBytes => true,
+            _ if text.is_null(v) => true,
+            DataType::OptU64 => parses::<u64>(v, text),
+            DataType::OptI64 => parses::<i64>(v, text),
+            DataType::OptF64 => parses::<f64>(v, text),
+            DataType::OptBool => parses::<bool>(v, text),
+            DataType::OptString => parses::<String>(v, text),
+            DataType::OptDate => parses::<NaiveDate>(v, text),
+            DataType::OptDateTime => parses::<NaiveDateTime>(v, text),
+            // producing lists fails anyway
+            DataType::OptI64List
+            | DataType::OptF64List
+            | DataType::OptBoolList
+            | DataType::OptStringList => true,
+        };
+        !valid
+    })
+}
+
+/// The csv file the rows skipped by `OnError::Skip` are appended to, preceded by the path and
+/// line they are read from. It is shared by all the sources of a builder, and only created once
+/// a row is rejected.
+pub(crate) struct RejectFile {
+    path: String,
+    writer: Mutex<Option<csv::Writer<File>>>,
+}
+
+impl RejectFile {
+    pub(crate) fn new(path: &str) -> Self {
+        RejectFile {
+            path: path.to_string(),
+            writer: Mutex::new(None),
+        }
+    }
+
+    fn reject(&self, source: &str, line: u64, record: &csv::ByteRecord) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.is_none() {
+            *writer = Some(
+                csv::WriterBuilder::new()
+                    .flexible(true)
+                    .from_path(&self.path)?,
+            );
+        }
+        let writer = writer.as_mut().unwrap();
+
+        let mut row = csv::ByteRecord::new();
+        row.push_field(source.as_bytes());
+        row.push_field(line.to_string().as_bytes());
+        row.extend(record.iter());
+        writer.write_byte_record(&row)?;
+        writer.flush().map_err(anyhow::Error::from)?;
+        Ok(())
     }
 }
 
 impl DataSource for CSVSource {
     type TypeSystem = DataType;
 
-    /// The parameter `query` is the path of the csv file
+    /// The parameter `query` is the path of the csv file, or a byte range of it as generated by
+    /// `CSVSourceBuilder::byte_ranges`, or several of those separated by
+    /// `files::FILE_SEPARATOR` to read one after the other. The header row is read from every
+    /// file, and the names of the first one are kept.
     fn run_query(&mut self, query: &str) -> Result<()> {
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_reader(File::open(query).expect("open file"));
-
-        self.records = reader.records().map(|v| v.expect("csv record")).collect();
-        self.nrows = self.records.len();
-        if self.nrows > 0 {
-            self.ncols = self.records[0].len();
+        if self.options.on_error == OnError::Skip && self.schema.is_none() {
+            throw!(anyhow!(
+                "skipping the rows that do not parse needs the schema of the file"
+            ));
         }
+        self.parts = files::split(query).map(String::from).collect();
+        self.paths = match self.opener {
+            Some(_) => self.parts.clone(),
+            None => self
+                .parts
+                .iter()
+                .map(|part| Ok(parse_query(part)?.0.to_string()))
+                .collect::<Result<_>>()?,
+        };
+        self.file = 0;
+        let mut reader = self.options.open(&self.parts[0], self.opener.as_ref())?;
+        self.headers = reader.headers.take();
+        self.reader = Some(reader);
+
+        self.read_batch()?;
+        let filename = self.options.filename_column.as_ref();
+        self.ncols = match (&mut self.headers, &self.schema) {
+            (Some(headers), _) => {
+                headers.extend(filename.cloned());
+                headers.len()
+            }
+            (None, Some(schema)) => schema.len(),
+            (None, None) if self.nrows > 0 => self.records[0].len() + filename.iter().count(),
+            (None, None) => 0,
+        };
         Ok(())
     }
 
     fn nrows(&self) -> usize {
         self.nrows
     }
-}
 
-impl Produce<u64> for CSVSource {
-    fn produce(&mut self) -> Result<u64> {
-        let v: &str = self.records[self.counter / self.ncols][self.counter % self.ncols].as_ref();
-        self.counter += 1;
-        Ok(v.parse().unwrap_or_default())
+    /// The size of the fields read so far, without delimiters and quotes.
+    fn nbytes(&self) -> usize {
+        self.nbytes
     }
-}
 
-impl Produce<f64> for CSVSource {
-    fn produce(&mut self) -> Result<f64> {
-        let v: &str = self.records[self.counter / self.ncols][self.counter % self.ncols].as_ref();
-        self.counter += 1;
-        Ok(v.parse().unwrap_or_default())
+    fn fetch_next(&mut self) -> Result<usize> {
+        self.read_batch()
     }
-}
 
-impl Produce<bool> for CSVSource {
-    fn produce(&mut self) -> Result<bool> {
-        let v: &str = self.records[self.counter / self.ncols][self.counter % self.ncols].as_ref();
-        self.counter += 1;
-        Ok(v.parse().unwrap_or_default())
+    /// Count the records of the file by scanning it without keeping them, or estimate the count
+    /// from the size of the file and the records at its start. Rows skipped by `OnError::Skip`
+    /// are counted too. Compressed files are always counted exactly, since their size says
+    /// little about the size of their records, and so are readers, which have no size.
+    fn count_rows(&mut self, query: &str, row_count: RowCount) -> Result<usize> {
+        files::split(query)
+            .map(|file| count_file(&self.options, self.opener.as_ref(), file, row_count))
+            .sum()
     }
 }
 
-impl Produce<String> for CSVSource {
-    fn produce(&mut self) -> Result<String> {
-        let v: &str = self.records[self.counter / self.ncols][self.counter % self.ncols].as_ref();
-        self.counter += 1;
-        Ok(String::from(v))
-    }
-}
+/// Count or estimate the rows of a single file, or of a byte range of it, see `count_rows`.
+fn count_file(
+    options: &CSVOptions,
+    opener: Option<&Opener>,
+    query: &str,
+    row_count: RowCount,
+) -> Result<usize> {
+    let mut records = options.open(query, opener)?;
+    let start = records.position();
 
-impl Produce<Option<u64>> for CSVSource {
-    fn produce(&mut self) -> Result<Option<u64>> {
-        let v: &str = self.records[self.counter / self.ncols][self.counter % self.ncols].as_ref();
-        self.counter += 1;
-        if v.is_empty() {
-            return Ok(None);
+    let limit = match row_count {
+        RowCount::Exact => usize::MAX,
+        RowCount::Estimate if records.compression != Compression::None || opener.is_some() => {
+            usize::MAX
+        }
+        RowCount::Estimate => ESTIMATE_SAMPLE_ROWS,
+        _ => throw!(ConnectorAgentError::UnsupportedRowCount(row_count)),
+    };
+    let mut record = csv::ByteRecord::new();
+    let mut n = 0;
+    while n < limit {
+        if !records.read(&mut record)? {
+            return Ok(n);
         }
-        Ok(Some(v.parse().unwrap_or_default()))
+        n += 1;
     }
+
+    let end = match records.end {
+        Some(end) => end,
+        None => {
+            let (path, _) = parse_query(query)?;
+            std::fs::metadata(path).map_err(anyhow::Error::from)?.len()
+        }
+    };
+    let sampled = records.position() - start;
+    Ok((n as f64 * end.saturating_sub(start) as f64 / sampled as f64) as usize)
 }
+
+impl_produce_text!(CSVSource);
+
+impl_produce_unsupported!(
+    CSVSource,
+    "CSVSource does not support list types!",
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
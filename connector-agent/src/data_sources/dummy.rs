@@ -3,6 +3,7 @@ use crate::data_order::DataOrder;
 use crate::errors::{ConnectorAgentError, Result};
 use crate::types::DataType;
 use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime};
 use fehler::{throw, throws};
 use num_traits::cast::FromPrimitive;
 
@@ -92,6 +93,30 @@ impl Produce<bool> for U64CounterSource {
     }
 }
 
+impl Produce<i64> for U64CounterSource {
+    fn produce(&mut self) -> Result<i64> {
+        let ret = self.counter;
+        self.counter += 1;
+        Ok(FromPrimitive::from_u64(ret).unwrap_or_default())
+    }
+}
+
+impl_produce_unsupported!(
+    U64CounterSource,
+    "U64CounterSource only support numbers!",
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<NaiveDate>,
+    Option<NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
+
 pub struct StringSourceBuilder {}
 
 impl SourceBuilder for StringSourceBuilder {
@@ -185,6 +210,23 @@ impl Produce<bool> for StringSource {
     }
 }
 
+impl_produce_unsupported!(
+    StringSource,
+    "StringSource only support string!",
+    i64,
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<NaiveDate>,
+    Option<NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
+
 pub struct BoolSourceBuilder {}
 
 impl SourceBuilder for BoolSourceBuilder {
@@ -265,6 +307,23 @@ impl Produce<String> for BoolCounterSource {
         throw!(anyhow!("StringSource only support string!"))
     }
 }
+
+impl_produce_unsupported!(
+    BoolCounterSource,
+    "BoolCounterSource only support bool!",
+    i64,
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<NaiveDate>,
+    Option<NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
 pub struct F64SourceBuilder {}
 
 impl SourceBuilder for F64SourceBuilder {
@@ -347,6 +406,30 @@ impl Produce<String> for F64CounterSource {
     }
 }
 
+impl Produce<i64> for F64CounterSource {
+    fn produce(&mut self) -> Result<i64> {
+        let ret = self.counter;
+        self.counter += 0.5;
+        Ok(FromPrimitive::from_f64(ret).unwrap_or_default())
+    }
+}
+
+impl_produce_unsupported!(
+    F64CounterSource,
+    "F64CounterSource only support f64!",
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<NaiveDate>,
+    Option<NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
+
 pub struct OptU64SourceBuilder {
     fake_values: Vec<Vec<Option<u64>>>,
     ncols: usize,
@@ -438,3 +521,20 @@ impl Produce<String> for OptU64TestSource {
         throw!(anyhow!("Only Option<u64> is supported"));
     }
 }
+
+impl_produce_unsupported!(
+    OptU64TestSource,
+    "Only Option<u64> is supported",
+    i64,
+    Option<i64>,
+    Option<f64>,
+    Option<bool>,
+    Option<String>,
+    Option<NaiveDate>,
+    Option<NaiveDateTime>,
+    Option<Vec<u8>>,
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
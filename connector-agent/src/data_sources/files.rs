@@ -0,0 +1,88 @@
+use crate::errors::{ConnectorAgentError, Result};
+use anyhow::anyhow;
+use fehler::{throw, throws};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Separates the files of a query that reads several files one after the other.
+pub const FILE_SEPARATOR: char = '\n';
+
+/// The paths of the files `pattern` stands for, in order: the files matching it if it is a glob
+/// such as `data/2021-*.csv`, the files in it if it is a directory, or else the path itself.
+/// The paths found by a glob or in a directory are spelled alike, without `.` components, as in
+/// `data/2021-01.csv` for both `./data/2021-*.csv` and `./data`. Fails if a glob or a directory
+/// holds no file.
+#[throws(ConnectorAgentError)]
+pub fn expand(pattern: &str) -> Vec<String> {
+    let mut paths = vec![];
+    if pattern.contains(|c| matches!(c, '*' | '?' | '[')) {
+        for entry in glob::glob(pattern).map_err(anyhow::Error::from)? {
+            let path = entry.map_err(anyhow::Error::from)?;
+            if path.is_file() {
+                paths.push(path_to_string(&normalize(&path))?);
+            }
+        }
+    } else if Path::new(pattern).is_dir() {
+        for entry in fs::read_dir(pattern).map_err(anyhow::Error::from)? {
+            let path = entry.map_err(anyhow::Error::from)?.path();
+            if path.is_file() {
+                paths.push(path_to_string(&normalize(&path))?);
+            }
+        }
+    } else {
+        return vec![pattern.to_string()];
+    }
+
+    if paths.is_empty() {
+        throw!(anyhow!("no file matches {}", pattern));
+    }
+    paths.sort();
+    paths
+}
+
+/// Group consecutive files into queries that read about `size` bytes each. Files of `size`
+/// bytes or more are read by a query of their own.
+#[throws(ConnectorAgentError)]
+pub fn group_by_size(paths: &[String], size: u64) -> Vec<String> {
+    let mut groups = vec![];
+    let mut group: Vec<&str> = vec![];
+    let mut group_size = 0;
+    for path in paths {
+        let len = fs::metadata(path).map_err(anyhow::Error::from)?.len();
+        if !group.is_empty() && group_size + len > size {
+            groups.push(join(&group));
+            group.clear();
+            group_size = 0;
+        }
+        group.push(path);
+        group_size += len;
+    }
+    if !group.is_empty() {
+        groups.push(join(&group));
+    }
+    groups
+}
+
+/// A query reading `paths` one after the other.
+pub fn join(paths: &[&str]) -> String {
+    paths.join(&FILE_SEPARATOR.to_string())
+}
+
+/// The files a query reads one after the other.
+pub fn split(query: &str) -> impl Iterator<Item = &str> {
+    query.split(FILE_SEPARATOR)
+}
+
+/// `path` without its `.` components.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect()
+}
+
+fn path_to_string(path: &Path) -> Result<String> {
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => throw!(anyhow!("the path {:?} is not UTF-8", path)),
+    }
+}
@@ -0,0 +1,143 @@
+use crate::errors::{ConnectorAgentError, Result};
+use anyhow::anyhow;
+use fehler::{throw, throws};
+use std::io::BufRead;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Start,
+    Items,
+    End,
+}
+
+/// Reads the items of a top-level JSON array one at a time, so that the array is never held in
+/// memory whole. The bytes of every item are found by tracking the nesting of brackets and braces
+/// outside of strings, and are left for serde to parse.
+pub struct ArrayReader<R> {
+    reader: R,
+    state: State,
+    buf: Vec<u8>,
+    // the lines read so far, and the line the last item starts at
+    lines: u64,
+    item_line: u64,
+}
+
+impl<R: BufRead> ArrayReader<R> {
+    pub fn new(reader: R) -> Self {
+        ArrayReader {
+            reader,
+            state: State::Start,
+            buf: vec![],
+            lines: 1,
+            item_line: 0,
+        }
+    }
+
+    /// The bytes of the last item read, for serde to parse.
+    pub fn item(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// The line the last item read starts at, counting from 1.
+    pub fn line(&self) -> u64 {
+        self.item_line
+    }
+
+    /// Read the next item, or return false after the last one.
+    #[throws(ConnectorAgentError)]
+    pub fn read_item(&mut self) -> bool {
+        if self.state == State::Start {
+            match self.skip_whitespace()? {
+                Some(b'[') => self.reader.consume(1),
+                _ => throw!(anyhow!("expected a JSON array at line {}", self.lines)),
+            }
+            self.state = State::Items;
+            if self.skip_whitespace()? == Some(b']') {
+                self.reader.consume(1);
+                self.state = State::End;
+            }
+        }
+        if self.state == State::End {
+            return false;
+        }
+
+        self.skip_whitespace()?;
+        self.item_line = self.lines;
+        self.buf.clear();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        loop {
+            let chunk = self.reader.fill_buf().map_err(anyhow::Error::from)?;
+            if chunk.is_empty() {
+                throw!(anyhow!("the JSON array ends early at line {}", self.lines));
+            }
+            let mut end = None;
+            for (i, &b) in chunk.iter().enumerate() {
+                if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                    }
+                    continue;
+                }
+                match b {
+                    b'"' => in_string = true,
+                    b'[' | b'{' => depth += 1,
+                    b']' | b'}' if depth > 0 => depth -= 1,
+                    b',' | b']' if depth == 0 => {
+                        end = Some((i, b));
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            let len = end.map_or(chunk.len(), |(i, _)| i);
+            self.buf.extend_from_slice(&chunk[..len]);
+            self.lines += chunk[..len].iter().filter(|&&b| b == b'\n').count() as u64;
+            match end {
+                Some((_, b)) => {
+                    self.reader.consume(len + 1);
+                    if b == b']' {
+                        self.state = State::End;
+                    }
+                    break;
+                }
+                None => self.reader.consume(len),
+            }
+        }
+
+        if self.buf.iter().all(|b| b.is_ascii_whitespace()) {
+            throw!(anyhow!(
+                "empty item in the JSON array at line {}",
+                self.item_line
+            ));
+        }
+        true
+    }
+
+    /// Skip whitespace and return the next byte without consuming it.
+    fn skip_whitespace(&mut self) -> Result<Option<u8>> {
+        loop {
+            let chunk = self.reader.fill_buf().map_err(anyhow::Error::from)?;
+            if chunk.is_empty() {
+                return Ok(None);
+            }
+            match chunk.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(i) => {
+                    self.lines += chunk[..i].iter().filter(|&&b| b == b'\n').count() as u64;
+                    let next = chunk[i];
+                    self.reader.consume(i);
+                    return Ok(Some(next));
+                }
+                None => {
+                    let len = chunk.len();
+                    self.lines += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+}
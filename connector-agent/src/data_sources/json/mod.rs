@@ -0,0 +1,577 @@
+mod array;
+mod path;
+mod value_map;
+
+pub use self::array::ArrayReader;
+pub use self::path::{Columns, FieldPath};
+pub use self::value_map::ValueMap;
+use super::compression::{Compression, Decoded};
+use super::csv::BATCH_ROWS;
+use super::reader::{opener, Opener};
+use super::text::{FromText, TextOptions};
+use super::{DataSource, Produce, SourceBuilder};
+use crate::data_order::DataOrder;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
+use crate::types::DataType;
+use chrono::{NaiveDate, NaiveDateTime};
+use fehler::{throw, throws};
+use serde_json::{Map, Value};
+use std::io::{self, BufRead, Read, Write};
+use strum::EnumString;
+
+/// How the objects of a JSON input are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+pub enum JsonFormat {
+    /// Newline-delimited JSON, one object to a line.
+    JsonL,
+    /// A single top-level array of objects, read one object at a time.
+    Array,
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        JsonFormat::JsonL
+    }
+}
+
+/// The objects of a JSON input, with the line each of them starts at.
+enum Objects {
+    Lines {
+        reader: Decoded,
+        buf: String,
+        line: u64,
+    },
+    Array(ArrayReader<Decoded>),
+}
+
+impl Objects {
+    fn new(reader: Decoded, format: JsonFormat) -> Self {
+        match format {
+            JsonFormat::JsonL => Objects::Lines {
+                reader,
+                buf: String::new(),
+                line: 0,
+            },
+            JsonFormat::Array => Objects::Array(ArrayReader::new(reader)),
+        }
+    }
+
+    /// Read the bytes of the next object and the line it starts at, skipping blank lines, or
+    /// return `None` at the end of the input.
+    #[throws(ConnectorAgentError)]
+    fn next(&mut self) -> Option<(&[u8], u64)> {
+        match self {
+            Objects::Lines { reader, buf, line } => loop {
+                buf.clear();
+                if reader.read_line(buf).map_err(anyhow::Error::from)? == 0 {
+                    return None;
+                }
+                *line += 1;
+                if !buf.trim().is_empty() {
+                    break Some((buf.as_bytes(), *line));
+                }
+            },
+            Objects::Array(reader) => match reader.read_item()? {
+                true => Some((reader.item(), reader.line())),
+                false => None,
+            },
+        }
+    }
+}
+
+/// Rewrite the JSON `input` laid out as `format` into newline-delimited JSON, mapping the values
+/// of the objects with `value_map`. Objects are read and written one at a time. Given `columns`,
+/// the objects are flattened into their rows, written as objects keyed by the column names.
+#[throws(ConnectorAgentError)]
+pub fn write_jsonl<R, W>(
+    input: R,
+    format: JsonFormat,
+    value_map: &ValueMap,
+    columns: Option<&Columns>,
+    mut output: W,
+) where
+    R: Read + Send + 'static,
+    W: Write,
+{
+    let (reader, _) = Compression::None.decode("", Box::new(input))?;
+    let mut objects = Objects::new(reader, format);
+    let mut values = vec![];
+    while let Some((raw, line)) = objects.next()? {
+        let mut object: Map<String, Value> =
+            serde_json::from_slice(raw).map_err(|source| ConnectorAgentError::JsonError {
+                path: String::new(),
+                line,
+                source,
+            })?;
+        let columns = match columns {
+            Some(columns) => columns,
+            None => {
+                value_map.apply_object(&mut object);
+                serde_json::to_writer(&mut output, &object).map_err(anyhow::Error::from)?;
+                output.write_all(b"\n").map_err(anyhow::Error::from)?;
+                continue;
+            }
+        };
+
+        values.clear();
+        columns
+            .flatten(object, value_map, &mut values)
+            .map_err(|value| not_an_array(columns, "", line, &value))?;
+        for row in values.chunks_mut(columns.len()) {
+            let row: Map<String, Value> = row
+                .iter_mut()
+                .enumerate()
+                .map(|(col, value)| (columns.name(col).to_string(), std::mem::take(value)))
+                .collect();
+            serde_json::to_writer(&mut output, &row).map_err(anyhow::Error::from)?;
+            output.write_all(b"\n").map_err(anyhow::Error::from)?;
+        }
+    }
+}
+
+/// The error of an exploded array that is not an array.
+fn not_an_array(columns: &Columns, path: &str, line: u64, value: &Value) -> ConnectorAgentError {
+    ConnectorAgentError::JsonValueError {
+        path: path.to_string(),
+        line,
+        field: columns.exploded().unwrap_or_default().to_string(),
+        value: value.to_string(),
+        ty: "array",
+    }
+}
+
+/// Builds `JsonSource`s, where the query of every partition is the path of a file, or the name of
+/// a reader if a factory of readers is given.
+pub struct JsonSourceBuilder {
+    columns: Columns,
+    format: JsonFormat,
+    value_map: ValueMap,
+    compression: Compression,
+    batch_size: usize,
+    eager: bool,
+    open: Option<Opener>,
+}
+
+impl JsonSourceBuilder {
+    /// Read the values at the paths `columns` of every object into the columns of the schema, in
+    /// order, e.g. `name`, `meta.instrument` or `asks[*].price`. Other fields are ignored. See
+    /// `Columns` for how arrays are exploded into rows.
+    #[throws(ConnectorAgentError)]
+    pub fn new(columns: &[&str]) -> Self {
+        JsonSourceBuilder {
+            columns: Columns::new(columns)?,
+            format: JsonFormat::default(),
+            value_map: ValueMap::default(),
+            compression: Compression::default(),
+            batch_size: BATCH_ROWS,
+            eager: false,
+            open: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: JsonFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Map the values of the columns with `value_map` before they are read, e.g. to read codes
+    /// spelled as strings in integer columns. The fields the paths of the columns start with are
+    /// the fields of the mapping.
+    pub fn with_value_map(mut self, value_map: ValueMap) -> Self {
+        self.value_map = value_map;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Read the objects `batch_size` at a time while the rows are written, instead of the
+    /// default of 65536.
+    #[throws(ConnectorAgentError)]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Read the whole input when its query runs instead of in batches, ignoring the batch size.
+    /// Memory then grows with the number of objects.
+    pub fn with_eager(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    /// Read the readers `open` returns for the queries instead of the files they name. The
+    /// readers may be opened again to count rows.
+    pub fn with_reader<F, R>(mut self, open: F) -> Self
+    where
+        F: Fn(&str) -> io::Result<R> + Send + Sync + 'static,
+        R: Read + Send + 'static,
+    {
+        self.open = Some(opener(open));
+        self
+    }
+
+    pub fn columns(&self) -> &Columns {
+        &self.columns
+    }
+}
+
+impl SourceBuilder for JsonSourceBuilder {
+    const DATA_ORDERS: &'static [DataOrder] = &[DataOrder::RowMajor];
+    type DataSource = JsonSource;
+
+    #[throws(ConnectorAgentError)]
+    fn set_data_order(&mut self, data_order: DataOrder) {
+        if !matches!(data_order, DataOrder::RowMajor) {
+            throw!(ConnectorAgentError::UnsupportedDataOrder(data_order))
+        }
+    }
+
+    fn build(&mut self) -> Self::DataSource {
+        JsonSource {
+            columns: self.columns.clone(),
+            format: self.format,
+            value_map: self.value_map.clone(),
+            compression: self.compression,
+            batch_size: if self.eager {
+                None
+            } else {
+                Some(self.batch_size)
+            },
+            open: self.open.clone(),
+            path: String::new(),
+            objects: None,
+            values: vec![],
+            row_lines: vec![],
+            counter: 0,
+            nbytes: 0,
+            nrows: 0,
+        }
+    }
+}
+
+/// Reads JSON objects, one to a line or as the items of an array. The values at the paths of the
+/// columns are moved out of every object, or copied if other columns read them too, and missing
+/// values are null.
+pub struct JsonSource {
+    columns: Columns,
+    format: JsonFormat,
+    value_map: ValueMap,
+    compression: Compression,
+    batch_size: Option<usize>,
+    open: Option<Opener>,
+    path: String,
+    objects: Option<Objects>,
+    // the values of the rows of the current batch, row after row, moved out as they are produced
+    values: Vec<Value>,
+    // the line the object of every row of the batch is read from
+    row_lines: Vec<u64>,
+    counter: usize,
+    nbytes: usize,
+    pub nrows: usize,
+}
+
+impl JsonSource {
+    #[throws(ConnectorAgentError)]
+    fn open(&self, query: &str) -> Decoded {
+        match &self.open {
+            Some(open) => self.compression.decode(query, open(query)?)?.0,
+            None => self.compression.open(query)?.0,
+        }
+    }
+
+    /// Read the next object of the input and the line it starts at, or return `None` at the
+    /// end of the input.
+    fn read_object(&mut self) -> Result<Option<(Map<String, Value>, u64)>> {
+        let objects = match self.objects.as_mut() {
+            Some(objects) => objects,
+            None => return Ok(None),
+        };
+        let (raw, line) = match objects.next()? {
+            Some(next) => next,
+            None => {
+                self.objects = None;
+                return Ok(None);
+            }
+        };
+        self.nbytes += raw.len();
+        match serde_json::from_slice(raw) {
+            Ok(object) => Ok(Some((object, line))),
+            Err(source) => Err(ConnectorAgentError::JsonError {
+                path: self.path.clone(),
+                line,
+                source,
+            }),
+        }
+    }
+
+    fn read_batch(&mut self) -> Result<usize> {
+        self.values.clear();
+        self.row_lines.clear();
+        self.counter = 0;
+        let mut n = 0;
+        while self.batch_size.map_or(true, |size| n < size) {
+            let (object, line) = match self.read_object()? {
+                Some(next) => next,
+                None => break,
+            };
+            let rows = self
+                .columns
+                .flatten(object, &self.value_map, &mut self.values)
+                .map_err(|value| not_an_array(&self.columns, &self.path, line, &value))?;
+            self.row_lines.extend(std::iter::repeat(line).take(rows));
+            n += rows;
+        }
+        self.nrows = n;
+        Ok(n)
+    }
+
+    fn next_cell(&mut self) -> Result<(usize, usize)> {
+        let ncols = self.columns.len();
+        if self.counter >= self.nrows * ncols {
+            throw!(ConnectorAgentError::OutOfBound);
+        }
+        let cell = (self.counter / ncols, self.counter % ncols);
+        self.counter += 1;
+        Ok(cell)
+    }
+
+    /// Move the value of the next cell out of the batch.
+    fn next_value(&mut self) -> Result<((usize, usize), Value)> {
+        let cell = self.next_cell()?;
+        let value = std::mem::take(&mut self.values[cell.0 * self.columns.len() + cell.1]);
+        Ok((cell, value))
+    }
+
+    /// Nulls are errors, since the column is not nullable.
+    fn produce_value<T: FromJson>(&mut self) -> Result<T> {
+        let (cell, value) = self.next_value()?;
+        T::from_json(value).map_err(|value| self.value_error::<T>(cell, &value))
+    }
+
+    fn produce_option<T: FromJson>(&mut self) -> Result<Option<T>> {
+        match self.next_value()? {
+            (_, Value::Null) => Ok(None),
+            (cell, value) => T::from_json(value)
+                .map(Some)
+                .map_err(|value| self.value_error::<T>(cell, &value)),
+        }
+    }
+
+    fn value_error<T: FromJson>(
+        &self,
+        (row, col): (usize, usize),
+        value: &Value,
+    ) -> ConnectorAgentError {
+        ConnectorAgentError::JsonValueError {
+            path: self.path.clone(),
+            line: self.row_lines[row],
+            field: self.columns.name(col).to_string(),
+            value: value.to_string(),
+            ty: T::NAME,
+        }
+    }
+}
+
+impl DataSource for JsonSource {
+    type TypeSystem = DataType;
+
+    /// The parameter `query` is the path of the file, or the name of the reader to read.
+    fn run_query(&mut self, query: &str) -> Result<()> {
+        self.path = query.to_string();
+        self.objects = Some(Objects::new(self.open(query)?, self.format));
+        self.read_batch()?;
+        Ok(())
+    }
+
+    fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// The size of the objects read so far.
+    fn nbytes(&self) -> usize {
+        self.nbytes
+    }
+
+    fn fetch_next(&mut self) -> Result<usize> {
+        self.read_batch()
+    }
+
+    /// Count the objects without parsing them, which is exact even for `RowCount::Estimate`.
+    /// If the columns explode an array, the objects are parsed to count its items instead.
+    fn count_rows(&mut self, query: &str, row_count: RowCount) -> Result<usize> {
+        if !matches!(row_count, RowCount::Exact | RowCount::Estimate) {
+            throw!(ConnectorAgentError::UnsupportedRowCount(row_count));
+        }
+        let mut objects = Objects::new(self.open(query)?, self.format);
+        let mut n = 0;
+        while let Some((raw, line)) = objects.next()? {
+            if self.columns.exploded().is_none() {
+                n += 1;
+                continue;
+            }
+            let mut object: Map<String, Value> =
+                serde_json::from_slice(raw).map_err(|source| ConnectorAgentError::JsonError {
+                    path: query.to_string(),
+                    line,
+                    source,
+                })?;
+            n += self
+                .columns
+                .count(&mut object)
+                .map_err(|value| not_an_array(&self.columns, query, line, &value))?;
+        }
+        Ok(n)
+    }
+}
+
+/// A type the values of a JSON source are read as, taking the value back if it does not fit.
+trait FromJson: Sized {
+    /// The name of the type in errors.
+    const NAME: &'static str;
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value>;
+}
+
+impl FromJson for u64 {
+    const NAME: &'static str = "u64";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        value.as_u64().ok_or(value)
+    }
+}
+
+impl FromJson for i64 {
+    const NAME: &'static str = "i64";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        value.as_i64().ok_or(value)
+    }
+}
+
+/// Integers are read as floats too.
+impl FromJson for f64 {
+    const NAME: &'static str = "f64";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        value.as_f64().ok_or(value)
+    }
+}
+
+impl FromJson for bool {
+    const NAME: &'static str = "bool";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        value.as_bool().ok_or(value)
+    }
+}
+
+/// Strings are moved out as they are, and other values but nulls are read as their JSON text.
+impl FromJson for String {
+    const NAME: &'static str = "String";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        match value {
+            Value::String(s) => Ok(s),
+            Value::Null => Err(Value::Null),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Dates and timestamps are strings, spelled as in text-based sources.
+macro_rules! impl_from_json_by_text {
+    ($($t:ty),+) => {
+        $(
+            impl FromJson for $t {
+                const NAME: &'static str = <$t as FromText>::NAME;
+
+                fn from_json(value: Value) -> std::result::Result<Self, Value> {
+                    value
+                        .as_str()
+                        .and_then(|v| <$t>::from_text(v, &TextOptions::default()))
+                        .ok_or(value)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_json_by_text!(NaiveDate, NaiveDateTime);
+
+/// Bytes are the UTF-8 of strings.
+impl FromJson for Vec<u8> {
+    const NAME: &'static str = "bytes";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        match value {
+            Value::String(s) => Ok(s.into_bytes()),
+            value => Err(value),
+        }
+    }
+}
+
+/// Lists are arrays, with nulls for the null items.
+impl<T: FromJson> FromJson for Vec<Option<T>> {
+    const NAME: &'static str = "list";
+
+    fn from_json(value: Value) -> std::result::Result<Self, Value> {
+        let items = match value {
+            Value::Array(items) => items,
+            value => return Err(value),
+        };
+        items
+            .into_iter()
+            .map(|item| match item {
+                Value::Null => Ok(None),
+                item => T::from_json(item).map(Some),
+            })
+            .collect()
+    }
+}
+
+macro_rules! impl_produce {
+    ($($t:ty),+) => {
+        $(
+            impl Produce<$t> for JsonSource {
+                fn produce(&mut self) -> Result<$t> {
+                    self.produce_value()
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_produce_option {
+    ($($t:ty),+) => {
+        $(
+            impl Produce<Option<$t>> for JsonSource {
+                fn produce(&mut self) -> Result<Option<$t>> {
+                    self.produce_option()
+                }
+            }
+        )+
+    };
+}
+
+impl_produce!(u64, i64, f64, bool, String);
+impl_produce_option!(
+    u64,
+    i64,
+    f64,
+    bool,
+    String,
+    NaiveDate,
+    NaiveDateTime,
+    Vec<u8>,
+    Vec<Option<i64>>,
+    Vec<Option<f64>>,
+    Vec<Option<bool>>,
+    Vec<Option<String>>
+);
@@ -0,0 +1,280 @@
+use super::ValueMap;
+use crate::errors::{ConnectorAgentError, Result};
+use fehler::{throw, throws};
+use serde_json::{Map, Value};
+use std::mem;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+    /// `[*]`, every item of an array, each in a row of its own.
+    Each,
+}
+
+/// The path of a value inside JSON objects, such as `meta.instrument`, `asks[0].price` or
+/// `asks[*][1]`. Fields are separated by dots and the items of arrays are picked by their index
+/// in brackets, while `[*]` explodes an array into a row for each of its items. Fields whose
+/// names hold dots or brackets are quoted as JSON strings in brackets, e.g. `["a.b"].c`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldPath {
+    text: String,
+    segments: Vec<Segment>,
+    // where `[*]` starts in the text
+    each_at: Option<usize>,
+}
+
+impl FieldPath {
+    #[throws(ConnectorAgentError)]
+    pub fn parse(text: &str) -> Self {
+        let invalid = |reason| ConnectorAgentError::FieldPathError {
+            path: text.to_string(),
+            reason,
+        };
+
+        let mut segments = vec![];
+        let mut each_at = None;
+        let mut rest = text;
+        loop {
+            if let Some(inner) = rest.strip_prefix("[\"") {
+                let end = closing_quote(inner).ok_or_else(|| invalid("unclosed quote"))?;
+                let name = serde_json::from_str(&rest[1..end + 3])
+                    .map_err(|_| invalid("invalid quoted field"))?;
+                if !rest[end + 3..].starts_with(']') {
+                    throw!(invalid("expected ] after a quoted field"));
+                }
+                segments.push(Segment::Field(name));
+                rest = &rest[end + 4..];
+            } else if rest.starts_with('[') {
+                let close = rest.find(']').ok_or_else(|| invalid("unclosed bracket"))?;
+                let segment = match &rest[1..close] {
+                    "*" if each_at.is_some() => throw!(invalid("more than one [*]")),
+                    "*" => {
+                        each_at = Some(text.len() - rest.len());
+                        Segment::Each
+                    }
+                    index => Segment::Index(
+                        index
+                            .parse()
+                            .map_err(|_| invalid("an index must be a number or *"))?,
+                    ),
+                };
+                segments.push(segment);
+                rest = &rest[close + 1..];
+            } else {
+                if !segments.is_empty() {
+                    rest = rest
+                        .strip_prefix('.')
+                        .ok_or_else(|| invalid("expected . or ["))?;
+                }
+                let end = rest.find(&['.', '['][..]).unwrap_or(rest.len());
+                if end == 0 {
+                    throw!(invalid("empty field name"));
+                }
+                segments.push(Segment::Field(rest[..end].to_string()));
+                rest = &rest[end..];
+            }
+            if rest.is_empty() {
+                break;
+            }
+        }
+
+        if !matches!(segments[0], Segment::Field(_)) {
+            throw!(invalid("a path starts with a field"));
+        }
+        FieldPath {
+            text: text.to_string(),
+            segments,
+            each_at,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the path is a single field, read as it is.
+    pub fn is_field(&self) -> bool {
+        self.segments.len() == 1
+    }
+
+    /// The field of the objects the path starts with.
+    fn root(&self) -> &str {
+        match &self.segments[0] {
+            Segment::Field(field) => field,
+            _ => unreachable!("a path starts with a field"),
+        }
+    }
+
+    /// The path of the array exploded by `[*]`.
+    fn exploded(&self) -> Option<FieldPath> {
+        self.each_at.map(|at| FieldPath {
+            text: self.text[..at].to_string(),
+            segments: self
+                .segments
+                .iter()
+                .take_while(|s| **s != Segment::Each)
+                .cloned()
+                .collect(),
+            each_at: None,
+        })
+    }
+
+    /// The value at the path in `object`, taking the item `item` of the exploded array.
+    fn get_mut<'a>(
+        &self,
+        object: &'a mut Map<String, Value>,
+        item: usize,
+    ) -> Option<&'a mut Value> {
+        let mut value = object.get_mut(self.root())?;
+        for segment in &self.segments[1..] {
+            value = match (segment, value) {
+                (Segment::Field(field), Value::Object(object)) => object.get_mut(field)?,
+                (Segment::Index(i), Value::Array(items)) => items.get_mut(*i)?,
+                (Segment::Each, Value::Array(items)) => items.get_mut(item)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Whether the values at the paths may be the same or inside one another.
+    fn overlaps(&self, other: &FieldPath) -> bool {
+        self.segments
+            .iter()
+            .zip(&other.segments)
+            .all(|pair| match pair {
+                (Segment::Each, Segment::Index(_)) | (Segment::Index(_), Segment::Each) => true,
+                (a, b) => a == b,
+            })
+    }
+}
+
+/// The index of the quote closing a JSON string in `s`, which starts after the opening one.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The columns read out of JSON objects, each at a `FieldPath`. The columns with `[*]` must all
+/// explode the same array, and every object then makes a row for each item of the array, with
+/// the values of the other columns repeated. Objects where the array is empty, null or missing
+/// make no rows.
+#[derive(Clone, Debug)]
+pub struct Columns {
+    paths: Vec<FieldPath>,
+    // whether the value of a column can be moved out of the objects, as no other column reads it
+    owned: Vec<bool>,
+    explode: Option<FieldPath>,
+}
+
+impl Columns {
+    #[throws(ConnectorAgentError)]
+    pub fn new<S: AsRef<str>>(columns: &[S]) -> Self {
+        let paths = columns
+            .iter()
+            .map(|column| FieldPath::parse(column.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut explode: Option<FieldPath> = None;
+        for path in &paths {
+            match (path.exploded(), &explode) {
+                (Some(array), Some(first)) if array.segments != first.segments => {
+                    throw!(ConnectorAgentError::FieldPathError {
+                        path: path.text.clone(),
+                        reason: "the columns explode different arrays",
+                    })
+                }
+                (Some(array), None) => explode = Some(array),
+                _ => {}
+            }
+        }
+
+        let owned = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                paths
+                    .iter()
+                    .enumerate()
+                    .all(|(j, other)| i == j || !path.overlaps(other))
+            })
+            .collect();
+        Columns {
+            paths,
+            owned,
+            explode,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// The name of the column `col`, which is its path.
+    pub fn name(&self, col: usize) -> &str {
+        self.paths[col].as_str()
+    }
+
+    /// Whether every column is a field of the objects, so that they need no flattening.
+    pub fn is_flat(&self) -> bool {
+        self.paths.iter().all(FieldPath::is_field)
+    }
+
+    /// The path of the array exploded into rows, if any.
+    pub fn exploded(&self) -> Option<&str> {
+        self.explode.as_ref().map(FieldPath::as_str)
+    }
+
+    /// The number of rows `object` makes, or the value in place of the exploded array if it is
+    /// not an array.
+    pub fn count(&self, object: &mut Map<String, Value>) -> std::result::Result<usize, Value> {
+        let array = match &self.explode {
+            Some(array) => array,
+            None => return Ok(1),
+        };
+        match array.get_mut(object, 0) {
+            None | Some(Value::Null) => Ok(0),
+            Some(Value::Array(items)) => Ok(items.len()),
+            Some(value) => Err(mem::take(value)),
+        }
+    }
+
+    /// Push the values of the rows `object` makes onto `values`, row after row, mapping them with
+    /// the mapping of the field each path starts with. Returns the number of rows, or the value
+    /// in place of the exploded array if it is not an array. Missing values are null.
+    pub fn flatten(
+        &self,
+        mut object: Map<String, Value>,
+        value_map: &ValueMap,
+        values: &mut Vec<Value>,
+    ) -> std::result::Result<usize, Value> {
+        let rows = self.count(&mut object)?;
+        for item in 0..rows {
+            for (path, &owned) in self.paths.iter().zip(&self.owned) {
+                // the values outside of the exploded array are read again for the next rows
+                let take = owned && (path.each_at.is_some() || item + 1 == rows);
+                let mut value = match path.get_mut(&mut object, item) {
+                    Some(value) if take => mem::take(value),
+                    Some(value) => value.clone(),
+                    None => Value::Null,
+                };
+                value_map.apply(path.root(), &mut value);
+                values.push(value);
+            }
+        }
+        Ok(rows)
+    }
+}
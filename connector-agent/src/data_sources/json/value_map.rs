@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Replaces strings of JSON inputs by other values before they are read, e.g. the codes of an
+/// enumeration by numbers. A mapping applies to the strings anywhere inside the value of a field,
+/// including the items of arrays and the fields of nested objects. Single fields can be given
+/// mappings of their own, so that the same string can be kept in one field and replaced in
+/// another.
+///
+/// Deserializes from JSON such as `{"fields": {"asks": {"values": {"new": 10001}}}}`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValueMap {
+    /// The replacements of the strings of every field.
+    pub values: HashMap<String, Value>,
+    /// The mappings of single fields by name, used instead of this one.
+    pub fields: HashMap<String, ValueMap>,
+}
+
+impl ValueMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the strings equal to `from` by `to`.
+    pub fn with_value(mut self, from: &str, to: Value) -> Self {
+        self.values.insert(from.to_string(), to);
+        self
+    }
+
+    /// Map the values of the field `field` with `map` instead.
+    pub fn with_field(mut self, field: &str, map: ValueMap) -> Self {
+        self.fields.insert(field.to_string(), map);
+        self
+    }
+
+    /// The mapping of the field `field`.
+    pub fn field(&self, field: &str) -> &ValueMap {
+        self.fields.get(field).unwrap_or(self)
+    }
+
+    /// Whether the mapping leaves every value as it is.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.fields.values().all(|map| map.is_empty())
+    }
+
+    /// Map `value`, the value of the field `field`.
+    pub fn apply(&self, field: &str, value: &mut Value) {
+        self.field(field).replace(value);
+    }
+
+    /// Map the values of the fields of `object`.
+    pub fn apply_object(&self, object: &mut Map<String, Value>) {
+        for (field, value) in object.iter_mut() {
+            self.apply(field, value);
+        }
+    }
+
+    fn replace(&self, value: &mut Value) {
+        if self.values.is_empty() {
+            return;
+        }
+        match value {
+            Value::String(s) => {
+                if let Some(to) = self.values.get(s.as_str()) {
+                    *value = to.clone();
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.replace(item)),
+            Value::Object(object) => object.values_mut().for_each(|v| self.replace(v)),
+            _ => {}
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use super::{DataSource, Produce, SourceBuilder};
 use crate::data_order::DataOrder;
 use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
 use crate::types::DataType;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use fehler::{throw, throws};
 use num_traits::cast::FromPrimitive;
 
@@ -50,15 +52,25 @@ impl DataSource for MixedSource {
 
     // query: nrows,ncols
     fn run_query(&mut self, query: &str) -> Result<()> {
-        let v: Vec<usize> = query.split(',').map(|s| s.parse().unwrap()).collect();
-        self.nrows = v[0];
-        self.ncols = v[1];
+        let (nrows, ncols) = parse_query(query);
+        self.nrows = nrows;
+        self.ncols = ncols;
         Ok(())
     }
 
     fn nrows(&self) -> usize {
         self.nrows
     }
+
+    /// The number of rows is part of the query, so the count is always exact.
+    fn count_rows(&mut self, query: &str, _row_count: RowCount) -> Result<usize> {
+        Ok(parse_query(query).0)
+    }
+}
+
+fn parse_query(query: &str) -> (usize, usize) {
+    let v: Vec<usize> = query.split(',').map(|s| s.parse().unwrap()).collect();
+    (v[0], v[1])
 }
 
 impl Produce<u64> for MixedSource {
@@ -100,3 +112,102 @@ impl Produce<bool> for MixedSource {
         Ok(ret)
     }
 }
+
+impl Produce<i64> for MixedSource {
+    fn produce(&mut self) -> Result<i64> {
+        let ret = self.counter / self.ncols;
+        self.counter += 1;
+        Ok(FromPrimitive::from_usize(ret).unwrap_or_default())
+    }
+}
+
+impl Produce<Option<i64>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<i64>> {
+        let ret = self.counter / self.ncols;
+        self.counter += 1;
+        Ok(Some(FromPrimitive::from_usize(ret).unwrap_or_default()))
+    }
+}
+
+impl Produce<Option<f64>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<f64>> {
+        let ret = self.counter / self.ncols;
+        self.counter += 1;
+        Ok(Some(FromPrimitive::from_usize(ret).unwrap_or_default()))
+    }
+}
+
+impl Produce<Option<bool>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<bool>> {
+        let ret = (self.counter / self.ncols) % 2 == 0;
+        self.counter += 1;
+        Ok(Some(ret))
+    }
+}
+
+impl Produce<Option<String>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<String>> {
+        let ret = ((self.counter / self.ncols) as u64).to_string();
+        self.counter += 1;
+        Ok(Some(ret))
+    }
+}
+
+impl Produce<Option<NaiveDate>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<NaiveDate>> {
+        let ret =
+            NaiveDate::from_ymd(1970, 1, 1) + Duration::days((self.counter / self.ncols) as i64);
+        self.counter += 1;
+        Ok(Some(ret))
+    }
+}
+
+impl Produce<Option<NaiveDateTime>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<NaiveDateTime>> {
+        let ret = NaiveDateTime::from_timestamp((self.counter / self.ncols) as i64, 0);
+        self.counter += 1;
+        Ok(Some(ret))
+    }
+}
+
+impl Produce<Option<Vec<u8>>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<Vec<u8>>> {
+        let ret = ((self.counter / self.ncols) as u64)
+            .to_string()
+            .into_bytes();
+        self.counter += 1;
+        Ok(Some(ret))
+    }
+}
+
+impl Produce<Option<Vec<Option<i64>>>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<Vec<Option<i64>>>> {
+        let ret = (self.counter / self.ncols) as i64;
+        self.counter += 1;
+        Ok(Some(vec![Some(ret)]))
+    }
+}
+
+impl Produce<Option<Vec<Option<f64>>>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<Vec<Option<f64>>>> {
+        let ret = (self.counter / self.ncols) as f64;
+        self.counter += 1;
+        Ok(Some(vec![Some(ret)]))
+    }
+}
+
+impl Produce<Option<Vec<Option<bool>>>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<Vec<Option<bool>>>> {
+        let ret = (self.counter / self.ncols) % 2 == 0;
+        self.counter += 1;
+        Ok(Some(vec![Some(ret)]))
+    }
+}
+
+impl Produce<Option<Vec<Option<String>>>> for MixedSource {
+    fn produce(&mut self) -> Result<Option<Vec<Option<String>>>> {
+        let ret = (self.counter / self.ncols).to_string();
+        self.counter += 1;
+        Ok(Some(vec![Some(ret)]))
+    }
+}
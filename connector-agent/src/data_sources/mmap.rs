@@ -0,0 +1,477 @@
+use super::compression::Compression;
+use super::csv::{CSVOptions, OnError, BATCH_ROWS};
+use super::text::{FromText, TextFields};
+use super::{DataSource, SourceBuilder};
+use crate::data_order::DataOrder;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
+use crate::types::DataType;
+use anyhow::anyhow;
+use fehler::{throw, throws};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+
+/// Builds `MmapCSVSource`s, where the query of every partition is the path of a file.
+#[derive(Default)]
+pub struct MmapCSVSourceBuilder {
+    options: CSVOptions,
+    batch_size: Option<usize>,
+    eager: bool,
+}
+
+impl MmapCSVSourceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the files in the dialect of `options`. Compressed files and `OnError::Skip` are not
+    /// supported.
+    pub fn with_options(options: CSVOptions) -> Self {
+        MmapCSVSourceBuilder {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Split `batch_size` records at a time into fields, instead of the default of 65536.
+    #[throws(ConnectorAgentError)]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Split every file whole into fields when its query runs instead of in batches, ignoring
+    /// the batch size. The spans of the fields then take memory in proportion to the file.
+    pub fn with_eager(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    pub fn options(&self) -> &CSVOptions {
+        &self.options
+    }
+}
+
+impl SourceBuilder for MmapCSVSourceBuilder {
+    const DATA_ORDERS: &'static [DataOrder] = &[DataOrder::RowMajor];
+    type DataSource = MmapCSVSource;
+
+    #[throws(ConnectorAgentError)]
+    fn set_data_order(&mut self, data_order: DataOrder) {
+        if !matches!(data_order, DataOrder::RowMajor) {
+            throw!(ConnectorAgentError::UnsupportedDataOrder(data_order))
+        }
+    }
+
+    fn build(&mut self) -> Self::DataSource {
+        let mut source = MmapCSVSource::with_options(self.options.clone());
+        if self.eager {
+            source.batch_size = None;
+        } else if let Some(batch_size) = self.batch_size {
+            source.batch_size = Some(batch_size);
+        }
+        source
+    }
+}
+
+/// Reads an uncompressed csv file mapped into memory. Records are split into the spans of their
+/// fields in the mapped bytes, and values are parsed right from those bytes, so that only string
+/// values and fields with escaped quotes are copied. Records are split `BATCH_ROWS` at a time
+/// as they are produced, unless the builder is made eager.
+///
+/// The file must not be modified while it is read, as the mapping would change under the source.
+pub struct MmapCSVSource {
+    options: CSVOptions,
+    batch_size: Option<usize>,
+    path: String,
+    // `None` for empty files, which cannot be mapped
+    mmap: Option<Mmap>,
+    // where the next record starts
+    pos: usize,
+    headers: Option<Vec<String>>,
+    // the fields of the rows of the current batch, row after row
+    fields: Vec<Field>,
+    // where every row of the batch starts, to tell the line of a parse error
+    row_starts: Vec<usize>,
+    record: Vec<Field>,
+    counter: usize,
+    nbytes: usize,
+    pub nrows: usize,
+    pub ncols: usize,
+}
+
+impl MmapCSVSource {
+    pub fn new() -> Self {
+        Self::with_options(CSVOptions::default())
+    }
+
+    pub fn with_options(options: CSVOptions) -> Self {
+        Self {
+            options,
+            batch_size: Some(BATCH_ROWS),
+            path: String::new(),
+            mmap: None,
+            pos: 0,
+            headers: None,
+            fields: vec![],
+            row_starts: vec![],
+            record: vec![],
+            counter: 0,
+            nbytes: 0,
+            nrows: 0,
+            ncols: 0,
+        }
+    }
+
+    /// The names of the columns of the file read, if it has a header row.
+    pub fn headers(&self) -> Option<&[String]> {
+        self.headers.as_deref()
+    }
+
+    fn data(&self) -> &[u8] {
+        self.mmap.as_deref().unwrap_or(&[])
+    }
+
+    /// Split the next batch of records into fields.
+    fn read_batch(&mut self) -> Result<usize> {
+        self.fields.clear();
+        self.row_starts.clear();
+        self.counter = 0;
+        let filename = self.options.filename_column.is_some() as usize;
+        let data = self.mmap.as_deref().unwrap_or(&[]);
+        let mut n = 0;
+        while self.batch_size.map_or(true, |size| n < size) {
+            self.record.clear();
+            let start = match read_record(data, &mut self.pos, &self.options, &mut self.record) {
+                Some(start) => start,
+                None => break,
+            };
+            if self.ncols == 0 {
+                self.ncols = self.record.len() + filename;
+            }
+            let width = self.ncols - filename;
+            if self.record.len() != width && !self.options.flexible {
+                throw!(anyhow!(
+                    "the record at line {} of {} has {} fields, expected {}",
+                    line_at(data, start),
+                    self.path,
+                    self.record.len(),
+                    width
+                ));
+            }
+            // missing fields are empty, and extra fields dropped
+            self.record.resize(width, Field::empty(self.pos));
+            self.fields.extend_from_slice(&self.record);
+            self.row_starts.push(start);
+            n += 1;
+        }
+        self.nrows = n;
+        Ok(n)
+    }
+}
+
+impl TextFields for MmapCSVSource {
+    fn options(&self) -> &CSVOptions {
+        &self.options
+    }
+
+    fn next_cell(&mut self) -> Result<(usize, usize)> {
+        if self.counter >= self.nrows * self.ncols {
+            throw!(ConnectorAgentError::OutOfBound);
+        }
+        let cell = (self.counter / self.ncols, self.counter % self.ncols);
+        self.counter += 1;
+        self.nbytes += self.field(cell).len();
+        Ok(cell)
+    }
+
+    /// Borrowed from the mapped file unless quotes need unescaping.
+    fn field(&self, (row, col): (usize, usize)) -> Cow<'_, [u8]> {
+        let width = self.ncols - self.options.filename_column.is_some() as usize;
+        if col == width {
+            return Cow::Borrowed(self.path.as_bytes());
+        }
+        self.fields[row * width + col].bytes(self.data(), &self.options)
+    }
+
+    fn parse_error<T: FromText>(&self, (row, col): (usize, usize)) -> ConnectorAgentError {
+        ConnectorAgentError::CSVParseError {
+            path: self.path.clone(),
+            line: line_at(self.data(), self.row_starts[row]),
+            col,
+            text: String::from_utf8_lossy(&self.field((row, col))).into_owned(),
+            ty: T::NAME,
+        }
+    }
+}
+
+impl DataSource for MmapCSVSource {
+    type TypeSystem = DataType;
+
+    /// The parameter `query` is the path of the csv file.
+    fn run_query(&mut self, query: &str) -> Result<()> {
+        if self.options.on_error == OnError::Skip {
+            throw!(anyhow!("MmapCSVSource does not support OnError::Skip"));
+        }
+        self.path = query.to_string();
+        self.mmap = map(query, &self.options)?;
+        self.pos = 0;
+        self.ncols = 0;
+        self.headers = None;
+
+        let data = self.mmap.as_deref().unwrap_or(&[]);
+        self.pos = skip_lines(data, self.options.skip_rows);
+        if self.options.has_headers {
+            self.record.clear();
+            read_record(data, &mut self.pos, &self.options, &mut self.record);
+            let mut headers: Vec<_> = self
+                .record
+                .iter()
+                .map(|field| {
+                    String::from_utf8_lossy(&field.bytes(data, &self.options)).into_owned()
+                })
+                .collect();
+            headers.extend(self.options.filename_column.clone());
+            self.ncols = headers.len();
+            self.headers = Some(headers);
+        }
+
+        self.read_batch()?;
+        Ok(())
+    }
+
+    fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// The size of the fields read so far, without delimiters and quotes.
+    fn nbytes(&self) -> usize {
+        self.nbytes
+    }
+
+    fn fetch_next(&mut self) -> Result<usize> {
+        self.read_batch()
+    }
+
+    /// Count the records of the file by splitting them into fields without parsing them. The
+    /// count is exact even for `RowCount::Estimate`, since scanning a mapped file is cheap.
+    fn count_rows(&mut self, query: &str, row_count: RowCount) -> Result<usize> {
+        if !matches!(row_count, RowCount::Exact | RowCount::Estimate) {
+            throw!(ConnectorAgentError::UnsupportedRowCount(row_count));
+        }
+        let mmap = map(query, &self.options)?;
+        let data = mmap.as_deref().unwrap_or(&[]);
+        let mut pos = skip_lines(data, self.options.skip_rows);
+        let mut record = vec![];
+        if self.options.has_headers {
+            read_record(data, &mut pos, &self.options, &mut record);
+        }
+        let mut n = 0;
+        while read_record(data, &mut pos, &self.options, &mut record).is_some() {
+            record.clear();
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Map the file at `path` into memory, or return `None` if it is empty. Fails if the file is
+/// compressed according to `options`.
+#[throws(ConnectorAgentError)]
+fn map(path: &str, options: &CSVOptions) -> Option<Mmap> {
+    let file = File::open(path).map_err(anyhow::Error::from)?;
+    if file.metadata().map_err(anyhow::Error::from)?.len() == 0 {
+        return None;
+    }
+    // the file is assumed not to change while it is read, see `MmapCSVSource`
+    let mmap = unsafe { Mmap::map(&file) }.map_err(anyhow::Error::from)?;
+    let compression = match options.compression {
+        Compression::Auto => Compression::detect(path, &mmap[..mmap.len().min(10)]),
+        compression => compression,
+    };
+    if compression != Compression::None {
+        throw!(anyhow!("cannot map the compressed file {}", path));
+    }
+    Some(mmap)
+}
+
+/// The span of a field in the mapped file, without its quotes.
+#[derive(Clone, Copy, Debug)]
+struct Field {
+    start: usize,
+    end: usize,
+    /// The field holds escaped quotes, which need unescaping.
+    escaped: bool,
+}
+
+impl Field {
+    fn empty(pos: usize) -> Self {
+        Field {
+            start: pos,
+            end: pos,
+            escaped: false,
+        }
+    }
+
+    fn bytes<'a>(&self, data: &'a [u8], options: &CSVOptions) -> Cow<'a, [u8]> {
+        let bytes = &data[self.start..self.end];
+        if self.escaped {
+            Cow::Owned(unescape(bytes, options))
+        } else {
+            Cow::Borrowed(bytes)
+        }
+    }
+}
+
+/// The position after the first `n` lines of `data`.
+fn skip_lines(data: &[u8], n: usize) -> usize {
+    let mut pos = 0;
+    for _ in 0..n {
+        match data[pos..].iter().position(|&b| b == b'\n') {
+            Some(i) => pos += i + 1,
+            None => return data.len(),
+        }
+    }
+    pos
+}
+
+/// The line of `data` the byte at `pos` is on, counting from 1.
+fn line_at(data: &[u8], pos: usize) -> u64 {
+    data[..pos].iter().filter(|&&b| b == b'\n').count() as u64 + 1
+}
+
+/// Split the record starting at `pos` into the spans of its fields, leaving `pos` at the start
+/// of the next record. Empty lines and comments are skipped as the csv crate does, and the
+/// position the record starts at is returned, or `None` at the end of the file.
+fn read_record(
+    data: &[u8],
+    pos: &mut usize,
+    options: &CSVOptions,
+    fields: &mut Vec<Field>,
+) -> Option<usize> {
+    loop {
+        match data.get(*pos) {
+            None => return None,
+            Some(b'\n') | Some(b'\r') => *pos += 1,
+            Some(&c) if Some(c) == options.comment => {
+                *pos = match data[*pos..].iter().position(|&b| b == b'\n') {
+                    Some(i) => *pos + i + 1,
+                    None => data.len(),
+                }
+            }
+            Some(_) => break,
+        }
+    }
+
+    let start = *pos;
+    loop {
+        fields.push(read_field(data, pos, options));
+        match data.get(*pos) {
+            Some(&c) if c == options.delimiter => *pos += 1,
+            Some(b'\r') if data.get(*pos + 1) == Some(&b'\n') => {
+                *pos += 2;
+                break;
+            }
+            Some(_) => {
+                *pos += 1;
+                break;
+            }
+            None => break,
+        }
+    }
+    Some(start)
+}
+
+/// Read the field starting at `pos`, leaving `pos` at the delimiter or line break after it.
+fn read_field(data: &[u8], pos: &mut usize, options: &CSVOptions) -> Field {
+    let is_end = |b: u8| b == options.delimiter || b == b'\n' || b == b'\r';
+    let is_space = |b: u8| b == b' ' || b == b'\t';
+    if options.trim {
+        while data.get(*pos).map_or(false, |&b| is_space(b)) {
+            *pos += 1;
+        }
+    }
+
+    if !options.quoting || data.get(*pos) != Some(&options.quote) {
+        let start = *pos;
+        while data.get(*pos).map_or(false, |&b| !is_end(b)) {
+            *pos += 1;
+        }
+        let mut end = *pos;
+        if options.trim {
+            while end > start && is_space(data[end - 1]) {
+                end -= 1;
+            }
+        }
+        return Field {
+            start,
+            end,
+            escaped: false,
+        };
+    }
+
+    let quote = options.quote;
+    let start = *pos + 1;
+    let mut i = start;
+    let mut escaped = false;
+    let end = loop {
+        match data.get(i) {
+            // an unterminated quote runs to the end of the file
+            None => break i,
+            Some(&b) if Some(b) == options.escape && data.get(i + 1) == Some(&quote) => {
+                escaped = true;
+                i += 2;
+            }
+            Some(&b) if b == quote => {
+                if options.escape.is_none() && data.get(i + 1) == Some(&quote) {
+                    escaped = true;
+                    i += 2;
+                } else {
+                    i += 1;
+                    break i - 1;
+                }
+            }
+            Some(_) => i += 1,
+        }
+    };
+    // anything between the closing quote and the delimiter is dropped
+    *pos = i;
+    while data.get(*pos).map_or(false, |&b| !is_end(b)) {
+        *pos += 1;
+    }
+    Field {
+        start,
+        end,
+        escaped,
+    }
+}
+
+/// The value of a quoted field with escaped quotes.
+fn unescape(bytes: &[u8], options: &CSVOptions) -> Vec<u8> {
+    let escape = options.escape.unwrap_or(options.quote);
+    let mut value = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == escape && bytes.get(i + 1) == Some(&options.quote) {
+            i += 1;
+        }
+        value.push(bytes[i]);
+        i += 1;
+    }
+    value
+}
+
+impl_produce_text!(MmapCSVSource);
+
+impl_produce_unsupported!(
+    MmapCSVSource,
+    "MmapCSVSource does not support list types!",
+    Option<Vec<Option<i64>>>,
+    Option<Vec<Option<f64>>>,
+    Option<Vec<Option<bool>>>,
+    Option<Vec<Option<String>>>
+);
@@ -1,13 +1,37 @@
 // When implementing a data source, be make sure to implement Queryable and
 // Producer for all supported types in crate::types::DataType.
 
+/// Implement `Produce<T>` for the types a source cannot produce, failing with `$msg`.
+macro_rules! impl_produce_unsupported {
+    ($source:ty, $msg:expr, $($t:ty),+) => {
+        $(
+            impl $crate::data_sources::Produce<$t> for $source {
+                fn produce(&mut self) -> $crate::errors::Result<$t> {
+                    fehler::throw!(anyhow::anyhow!($msg))
+                }
+            }
+        )+
+    };
+}
+
+// declared first, so that the sources below can use the macros it defines
+#[macro_use]
+pub mod text;
+
+pub mod compression;
 pub mod csv;
 pub mod dummy;
+pub mod files;
+pub mod json;
 pub mod mixed;
+pub mod mmap;
 pub mod postgres;
+pub mod reader;
 
+use crate::cancel::CancellationToken;
 use crate::data_order::DataOrder;
-use crate::errors::Result;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
 use crate::typesystem::{TypeAssoc, TypeSystem};
 
 pub trait SourceBuilder {
@@ -17,6 +41,12 @@ pub trait SourceBuilder {
 
     fn set_data_order(&mut self, data_order: DataOrder) -> Result<()>;
     fn build(&mut self) -> Self::DataSource;
+
+    /// The row count the `Dispatcher` runs with when `row_count` is asked for, e.g. another one
+    /// if the sources cannot count their rows that way.
+    fn row_count(&self, row_count: RowCount) -> RowCount {
+        row_count
+    }
 }
 
 /// In general, a `DataSource` abstracts the data source as a stream, which can produce
@@ -38,8 +68,34 @@ pub trait DataSource: Sized {
         self.produce()
     }
 
-    /// Number of rows this `DataSource` get.
+    /// Number of rows this `DataSource` get, i.e. the rows in the current batch if the source
+    /// fetches its result in batches.
     fn nrows(&self) -> usize;
+
+    /// Number of bytes of raw data read so far, for reporting progress. Sources that do not keep
+    /// track of it return zero.
+    fn nbytes(&self) -> usize {
+        0
+    }
+
+    /// Fetch the next batch of rows after all the rows counted by `nrows` are read, and return
+    /// the size of the new batch. Zero means the result is exhausted. Sources that read the whole
+    /// result in `run_query` do not need to implement this.
+    fn fetch_next(&mut self) -> Result<usize> {
+        Ok(0)
+    }
+
+    /// Interrupt the blocking calls of this source, such as running the query, once `token` is
+    /// cancelled. The dispatcher checks the token between batches anyway, so sources that only
+    /// read from memory do not need to implement this.
+    fn set_cancellation_token(&mut self, _token: CancellationToken) {}
+
+    /// Count the rows of the result of `query` without fetching it, exactly or as an estimate
+    /// according to `row_count`. Sources that cannot count return
+    /// `ConnectorAgentError::UnsupportedRowCount`.
+    fn count_rows(&mut self, _query: &str, row_count: RowCount) -> Result<usize> {
+        Err(ConnectorAgentError::UnsupportedRowCount(row_count))
+    }
 }
 
 /// A type implemented `Produce<T>` means that it can produce a value `T` by consuming part of it's raw data buffer.
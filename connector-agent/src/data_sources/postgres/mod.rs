@@ -0,0 +1,427 @@
+mod partition;
+mod typesystem;
+
+pub use self::partition::ctid_queries;
+pub use self::typesystem::pg_type_to_data_type;
+use self::typesystem::{
+    decode_bool, decode_bytes, decode_date, decode_datetime, decode_f64, decode_i64, decode_list,
+    decode_string, resolve_domain, DecodeResult, Raw,
+};
+use super::{DataSource, Produce, SourceBuilder};
+use crate::cancel::{CancellationToken, Timers};
+use crate::data_order::DataOrder;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::row_count::RowCount;
+use crate::types::DataType;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime};
+use fehler::{throw, throws};
+use postgres::{types::Type, Client, NoTls, Row};
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+type PgManager = PostgresConnectionManager<NoTls>;
+
+const CURSOR: &str = "connector_agent_cursor";
+
+pub struct PostgresSourceBuilder {
+    pool: Pool<PgManager>,
+    batch_size: Option<usize>,
+    query_timeout: Option<Duration>,
+}
+
+impl PostgresSourceBuilder {
+    /// Create a builder whose sources share a pool of at most `nconn` connections
+    /// to the database described by the connection string `conn`.
+    #[throws(ConnectorAgentError)]
+    pub fn new(conn: &str, nconn: usize) -> Self {
+        if nconn == 0 {
+            throw!(ConnectorAgentError::NotPositive("number of connections"));
+        }
+        let manager = PostgresConnectionManager::new(conn.parse()?, NoTls);
+        let pool = Pool::builder().max_size(nconn as u32).build(manager)?;
+        Self {
+            pool,
+            batch_size: None,
+            query_timeout: None,
+        }
+    }
+
+    /// Read query results through a server-side cursor, `batch_size` rows at a time,
+    /// instead of fetching the whole result of a query at once.
+    #[throws(ConnectorAgentError)]
+    pub fn with_cursor(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Send a cancel request for every query, or fetch from a cursor, that runs longer than
+    /// `timeout`. The source then fails with `ConnectorAgentError::Timeout`.
+    pub fn with_query_timeout(mut self, timeout: Duration) -> Self {
+        self.query_timeout = Some(timeout);
+        self
+    }
+
+    /// Prepare `query` without running it, and read the column names and their `DataType`s
+    /// from the statement description.
+    #[throws(ConnectorAgentError)]
+    pub fn describe(&self, query: &str) -> (Vec<String>, Vec<DataType>) {
+        let mut conn = self.pool.get()?;
+        let stmt = conn.prepare(query)?;
+
+        let mut names = vec![];
+        let mut schema = vec![];
+        for col in stmt.columns() {
+            names.push(col.name().to_string());
+            schema.push(pg_type_to_data_type(col.type_())?);
+        }
+        (names, schema)
+    }
+
+    /// Generate `npartitions` queries that together read all rows of `table`, splitting it
+    /// into ranges of heap pages by `ctid`. Unlike range partitioning this needs no indexed
+    /// column, and the ranges are balanced as long as the rows are spread evenly over the pages.
+    /// Since Postgres 14 each range is read with a TID range scan instead of a full scan.
+    #[throws(ConnectorAgentError)]
+    pub fn ctid_partitions(&self, table: &str, npartitions: usize) -> Vec<String> {
+        let mut conn = self.pool.get()?;
+        // `relpages` is only an estimate maintained by VACUUM and ANALYZE, and is zero
+        // for tables that have never been processed by either
+        let row = conn.query_one(
+            "SELECT relpages::bigint, \
+                    pg_relation_size(oid) / current_setting('block_size')::bigint \
+             FROM pg_class WHERE oid = $1::text::regclass",
+            &[&table],
+        )?;
+        let (relpages, size): (i64, i64) = (row.get(0), row.get(1));
+        let npages = if relpages > 0 { relpages } else { size };
+        ctid_queries(table, npages as u64, npartitions)
+    }
+}
+
+impl SourceBuilder for PostgresSourceBuilder {
+    const DATA_ORDERS: &'static [DataOrder] = &[DataOrder::RowMajor];
+    type DataSource = PostgresSource;
+
+    #[throws(ConnectorAgentError)]
+    fn set_data_order(&mut self, data_order: DataOrder) {
+        if !matches!(data_order, DataOrder::RowMajor) {
+            throw!(ConnectorAgentError::UnsupportedDataOrder(data_order))
+        }
+    }
+
+    fn build(&mut self) -> Self::DataSource {
+        PostgresSource::new(self.pool.clone(), self.batch_size, self.query_timeout)
+    }
+
+    /// With a cursor, `RowCount::Query` counts the rows of the first batch only, and every
+    /// query would hold a connection from planning until its rows are written, which blocks
+    /// once there are more queries than connections. The rows are not counted then.
+    fn row_count(&self, row_count: RowCount) -> RowCount {
+        match (self.batch_size, row_count) {
+            (Some(_), RowCount::Query) => RowCount::Unknown,
+            _ => row_count,
+        }
+    }
+}
+
+pub struct PostgresSource {
+    pool: Pool<PgManager>,
+    batch_size: Option<usize>,
+    query_timeout: Option<Duration>,
+    // times the queries if there is a timeout, on one thread for all of them
+    timers: Option<Timers>,
+    cancellation: CancellationToken,
+    // the connection holding the open cursor, if the result is not exhausted yet
+    cursor_conn: Option<PooledConnection<PgManager>>,
+    rows: Vec<Row>,
+    types: Vec<Type>,
+    counter: usize,
+    nrows: usize,
+    ncols: usize,
+    nbytes: usize,
+}
+
+impl PostgresSource {
+    pub fn new(
+        pool: Pool<PgManager>,
+        batch_size: Option<usize>,
+        query_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            pool,
+            batch_size,
+            query_timeout,
+            timers: query_timeout.map(|_| Timers::new()),
+            cancellation: CancellationToken::new(),
+            cursor_conn: None,
+            rows: vec![],
+            types: vec![],
+            counter: 0,
+            nrows: 0,
+            ncols: 0,
+            nbytes: 0,
+        }
+    }
+
+    /// Return the position of the next cell in row-major order and advance the counter.
+    fn next_cell(&mut self) -> (usize, usize) {
+        let ret = (self.counter / self.ncols, self.counter % self.ncols);
+        self.counter += 1;
+        ret
+    }
+
+    /// Decode the next cell with `decode`, passing it the domain resolved type of the column.
+    fn next_value<T>(&mut self, decode: fn(&Type, &[u8]) -> DecodeResult<T>) -> Result<Option<T>> {
+        let (row, col) = self.next_cell();
+        let v = match self.rows[row].try_get::<_, Option<Raw>>(col)? {
+            Some(Raw(raw)) => {
+                self.nbytes += raw.len();
+                Some(decode(&self.types[col], raw).map_err(|e| anyhow!(e))?)
+            }
+            None => None,
+        };
+        Ok(v)
+    }
+
+    fn unexpected_null(&self) -> ConnectorAgentError {
+        let (row, col) = (
+            (self.counter - 1) / self.ncols,
+            (self.counter - 1) % self.ncols,
+        );
+        anyhow!("unexpected NULL at row {}, column {}", row, col).into()
+    }
+}
+
+impl DataSource for PostgresSource {
+    type TypeSystem = DataType;
+
+    /// The parameter `query` is a SQL query. Without a cursor its full result is fetched into
+    /// memory, otherwise a cursor is declared for it and its first batch is fetched.
+    fn run_query(&mut self, query: &str) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let token = &self.cancellation;
+        let timeout = self.timers.as_ref().zip(self.query_timeout);
+        let stmt = interruptible(&mut conn, token, timeout, |conn| Ok(conn.prepare(query)?))?;
+        self.types = stmt
+            .columns()
+            .iter()
+            .map(|c| resolve_domain(c.type_()).clone())
+            .collect();
+        self.ncols = self.types.len();
+
+        if self.batch_size.is_none() {
+            self.rows =
+                interruptible(
+                    &mut conn,
+                    token,
+                    timeout,
+                    |conn| Ok(conn.query(&stmt, &[])?),
+                )?;
+            self.nrows = self.rows.len();
+            self.counter = 0;
+            return Ok(());
+        }
+
+        // a cursor only lives as long as the transaction it is declared in
+        let declare = format!(
+            "BEGIN READ ONLY; DECLARE {} NO SCROLL CURSOR FOR {}",
+            CURSOR,
+            query.trim_end().trim_end_matches(';')
+        );
+        if let Err(e) = interruptible(&mut conn, token, timeout, |conn| {
+            Ok(conn.batch_execute(declare.as_str())?)
+        }) {
+            let _ = conn.batch_execute("ROLLBACK");
+            throw!(e);
+        }
+        self.cursor_conn = Some(conn);
+        self.fetch_next()?;
+        Ok(())
+    }
+
+    fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// The size of the values decoded so far, in the binary format they are received in.
+    fn nbytes(&self) -> usize {
+        self.nbytes
+    }
+
+    fn fetch_next(&mut self) -> Result<usize> {
+        let (conn, batch_size) = match (self.cursor_conn.as_mut(), self.batch_size) {
+            (Some(conn), Some(batch_size)) => (conn, batch_size),
+            _ => return Ok(0),
+        };
+
+        let fetch = format!("FETCH {} FROM {}", batch_size, CURSOR);
+        let timeout = self.timers.as_ref().zip(self.query_timeout);
+        self.rows = interruptible(conn, &self.cancellation, timeout, |conn| {
+            Ok(conn.query(fetch.as_str(), &[])?)
+        })?;
+        self.nrows = self.rows.len();
+        self.counter = 0;
+        if self.nrows < batch_size {
+            let close = format!("CLOSE {}; COMMIT", CURSOR);
+            interruptible(conn, &self.cancellation, timeout, |conn| {
+                Ok(conn.batch_execute(close.as_str())?)
+            })?;
+            self.cursor_conn = None;
+        }
+        Ok(self.nrows)
+    }
+
+    fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = token;
+    }
+
+    /// An exact count runs `COUNT(*)` over the query. An estimate takes the number of rows
+    /// the planner expects from `EXPLAIN`, which is cheap but can be far off, e.g. for tables
+    /// that have not been analyzed recently.
+    fn count_rows(&mut self, query: &str, row_count: RowCount) -> Result<usize> {
+        let query = query.trim_end().trim_end_matches(';');
+        let mut conn = self.pool.get()?;
+        let token = &self.cancellation;
+        let timeout = self.timers.as_ref().zip(self.query_timeout);
+        let query_one = |conn: &mut Client, sql: String| {
+            interruptible(conn, token, timeout, |conn| {
+                Ok(conn.query_one(sql.as_str(), &[])?)
+            })
+        };
+        let n = match row_count {
+            RowCount::Exact => {
+                let row = query_one(
+                    &mut conn,
+                    format!("SELECT COUNT(*) FROM ({}) AS connector_agent_count", query),
+                )?;
+                row.get::<_, i64>(0) as usize
+            }
+            RowCount::Estimate => {
+                let row = query_one(&mut conn, format!("EXPLAIN (FORMAT JSON) {}", query))?;
+                let Raw(plan) = row.try_get(0)?;
+                let plan: serde_json::Value =
+                    serde_json::from_slice(plan).map_err(anyhow::Error::from)?;
+                plan[0]["Plan"]["Plan Rows"]
+                    .as_f64()
+                    .ok_or_else(|| anyhow!("no row estimate in the query plan {}", plan))?
+                    as usize
+            }
+            _ => throw!(ConnectorAgentError::UnsupportedRowCount(row_count)),
+        };
+        Ok(n)
+    }
+}
+
+/// Run `f` on `conn`, sending a cancel request for the query it runs once `token` is cancelled
+/// or `timeout` elapses, as timed by `Timers`, in which case the query fails with `Cancelled` or
+/// `Timeout`.
+fn interruptible<T, F>(
+    conn: &mut Client,
+    token: &CancellationToken,
+    timeout: Option<(&Timers, Duration)>,
+    f: F,
+) -> Result<T>
+where
+    F: FnOnce(&mut Client) -> Result<T>,
+{
+    token.check()?;
+    let cancel = conn.cancel_token();
+    let on_cancel = cancel.clone();
+    let _guard = token.on_cancel(move || {
+        let _ = on_cancel.cancel_query(NoTls);
+    });
+    let timer = timeout.map(|(timers, timeout)| {
+        timers.start(timeout, move || {
+            let _ = cancel.cancel_query(NoTls);
+        })
+    });
+
+    match (f(conn), timer) {
+        (Err(_), _) if token.is_cancelled() => Err(ConnectorAgentError::Cancelled),
+        (Err(_), Some(timer)) if timer.fired() => {
+            Err(ConnectorAgentError::Timeout(timer.timeout()))
+        }
+        (ret, _) => ret,
+    }
+}
+
+impl Drop for PostgresSource {
+    /// Do not return a connection in the middle of a transaction to the pool.
+    fn drop(&mut self) {
+        if let Some(mut conn) = self.cursor_conn.take() {
+            let _ = conn.batch_execute("ROLLBACK");
+        }
+    }
+}
+
+/// Implement `Produce<Option<T>>` by decoding each cell with the given function.
+macro_rules! impl_produce_decoded {
+    ($($t:ty => $decode:expr,)+) => {
+        $(
+            impl Produce<Option<$t>> for PostgresSource {
+                fn produce(&mut self) -> Result<Option<$t>> {
+                    self.next_value($decode)
+                }
+            }
+        )+
+    };
+}
+
+impl_produce_decoded!(
+    i64 => decode_i64,
+    f64 => decode_f64,
+    bool => decode_bool,
+    String => decode_string,
+    NaiveDate => decode_date,
+    NaiveDateTime => decode_datetime,
+    Vec<u8> => decode_bytes,
+    Vec<Option<i64>> => |ty, raw| decode_list(ty, raw, decode_i64),
+    Vec<Option<f64>> => |ty, raw| decode_list(ty, raw, decode_f64),
+    Vec<Option<bool>> => |ty, raw| decode_list(ty, raw, decode_bool),
+    Vec<Option<String>> => |ty, raw| decode_list(ty, raw, decode_string),
+);
+
+impl Produce<Option<u64>> for PostgresSource {
+    fn produce(&mut self) -> Result<Option<u64>> {
+        match Produce::<Option<i64>>::produce(self)? {
+            Some(v) => Ok(Some(u64::try_from(v).map_err(anyhow::Error::from)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Produce<u64> for PostgresSource {
+    fn produce(&mut self) -> Result<u64> {
+        Produce::<Option<u64>>::produce(self)?.ok_or_else(|| self.unexpected_null())
+    }
+}
+
+impl Produce<i64> for PostgresSource {
+    fn produce(&mut self) -> Result<i64> {
+        Produce::<Option<i64>>::produce(self)?.ok_or_else(|| self.unexpected_null())
+    }
+}
+
+impl Produce<f64> for PostgresSource {
+    fn produce(&mut self) -> Result<f64> {
+        Produce::<Option<f64>>::produce(self)?.ok_or_else(|| self.unexpected_null())
+    }
+}
+
+impl Produce<bool> for PostgresSource {
+    fn produce(&mut self) -> Result<bool> {
+        Produce::<Option<bool>>::produce(self)?.ok_or_else(|| self.unexpected_null())
+    }
+}
+
+impl Produce<String> for PostgresSource {
+    fn produce(&mut self) -> Result<String> {
+        Produce::<Option<String>>::produce(self)?.ok_or_else(|| self.unexpected_null())
+    }
+}
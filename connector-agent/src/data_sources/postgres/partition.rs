@@ -0,0 +1,28 @@
+/// Split a scan over the `npages` heap pages of `table` into `npartitions` queries over
+/// consecutive ranges of pages. The first and last ranges are left open, so that rows in pages
+/// added since `npages` was estimated are still read.
+pub fn ctid_queries(table: &str, npages: u64, npartitions: usize) -> Vec<String> {
+    // there is no point in having more partitions than pages
+    let npartitions = (npartitions as u64).min(npages).max(1);
+
+    let bounds: Vec<u64> = (1..npartitions).map(|i| i * npages / npartitions).collect();
+
+    let mut queries = vec![];
+    for i in 0..=bounds.len() {
+        let mut conds = vec![];
+        if i > 0 {
+            conds.push(format!("ctid >= '({},0)'", bounds[i - 1]));
+        }
+        if i < bounds.len() {
+            conds.push(format!("ctid < '({},0)'", bounds[i]));
+        }
+
+        let query = if conds.is_empty() {
+            format!("SELECT * FROM {}", table)
+        } else {
+            format!("SELECT * FROM {} WHERE {}", table, conds.join(" AND "))
+        };
+        queries.push(query);
+    }
+    queries
+}
@@ -0,0 +1,169 @@
+use crate::errors::ConnectorAgentError;
+use crate::types::DataType;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use fehler::{throw, throws};
+use postgres::types::{FromSql, Kind, Type};
+use std::error::Error;
+
+pub(crate) type DecodeResult<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+/// Map the Postgres type of a result column to the `DataType` it will be read as.
+/// Postgres does not report the nullability of query results, so every column maps
+/// to a nullable type. Domains are read as their base type, enums as strings and
+/// one-dimensional arrays as lists.
+#[throws(ConnectorAgentError)]
+pub fn pg_type_to_data_type(ty: &Type) -> DataType {
+    let ty = resolve_domain(ty);
+    match ty.kind() {
+        Kind::Array(elem) => match scalar_data_type(resolve_domain(elem)) {
+            Some(DataType::OptI64) => DataType::OptI64List,
+            Some(DataType::OptF64) => DataType::OptF64List,
+            Some(DataType::OptBool) => DataType::OptBoolList,
+            Some(DataType::OptString) => DataType::OptStringList,
+            _ => throw!(unsupported(ty)),
+        },
+        _ => match scalar_data_type(ty) {
+            Some(dt) => dt,
+            None => throw!(unsupported(ty)),
+        },
+    }
+}
+
+fn scalar_data_type(ty: &Type) -> Option<DataType> {
+    let dt = match *ty {
+        Type::INT2 | Type::INT4 | Type::INT8 => DataType::OptI64,
+        Type::FLOAT4 | Type::FLOAT8 | Type::NUMERIC => DataType::OptF64,
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => DataType::OptString,
+        Type::BOOL => DataType::OptBool,
+        Type::DATE => DataType::OptDate,
+        Type::TIMESTAMP | Type::TIMESTAMPTZ => DataType::OptDateTime,
+        Type::BYTEA => DataType::OptBytes,
+        _ if matches!(ty.kind(), Kind::Enum(_)) => DataType::OptString,
+        _ => return None,
+    };
+    Some(dt)
+}
+
+fn unsupported(ty: &Type) -> ConnectorAgentError {
+    ConnectorAgentError::UnsupportedPostgresType(ty.name().to_string(), ty.oid())
+}
+
+/// Strip any domains from `ty`, returning the type its values are encoded as.
+pub(crate) fn resolve_domain(mut ty: &Type) -> &Type {
+    while let Kind::Domain(base) = ty.kind() {
+        ty = base;
+    }
+    ty
+}
+
+/// The binary encoding of a value of any type. `FromSql` impls reject domains and enums
+/// they do not know about, so values are read as `Raw` and decoded with the resolved type.
+pub(crate) struct Raw<'a>(pub &'a [u8]);
+
+impl<'a> FromSql<'a> for Raw<'a> {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> DecodeResult<Self> {
+        Ok(Raw(raw))
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
+pub(crate) fn decode_i64(ty: &Type, raw: &[u8]) -> DecodeResult<i64> {
+    match *ty {
+        Type::INT2 => i16::from_sql(ty, raw).map(i64::from),
+        Type::INT4 => i32::from_sql(ty, raw).map(i64::from),
+        _ => i64::from_sql(ty, raw),
+    }
+}
+
+pub(crate) fn decode_f64(ty: &Type, raw: &[u8]) -> DecodeResult<f64> {
+    match *ty {
+        Type::FLOAT4 => f32::from_sql(ty, raw).map(f64::from),
+        Type::NUMERIC => Numeric::from_sql(ty, raw).map(|n| n.0),
+        _ => f64::from_sql(ty, raw),
+    }
+}
+
+pub(crate) fn decode_bool(ty: &Type, raw: &[u8]) -> DecodeResult<bool> {
+    bool::from_sql(ty, raw)
+}
+
+/// Text types and enum labels are both sent as plain UTF-8.
+pub(crate) fn decode_string(_: &Type, raw: &[u8]) -> DecodeResult<String> {
+    String::from_sql(&Type::TEXT, raw)
+}
+
+pub(crate) fn decode_date(ty: &Type, raw: &[u8]) -> DecodeResult<NaiveDate> {
+    NaiveDate::from_sql(ty, raw)
+}
+
+pub(crate) fn decode_datetime(ty: &Type, raw: &[u8]) -> DecodeResult<NaiveDateTime> {
+    match *ty {
+        Type::TIMESTAMPTZ => DateTime::<Utc>::from_sql(ty, raw).map(|dt| dt.naive_utc()),
+        _ => NaiveDateTime::from_sql(ty, raw),
+    }
+}
+
+pub(crate) fn decode_bytes(ty: &Type, raw: &[u8]) -> DecodeResult<Vec<u8>> {
+    Vec::<u8>::from_sql(ty, raw)
+}
+
+/// Decode a one-dimensional array of the (domain resolved) array type `ty`,
+/// decoding each element with `decode`.
+pub(crate) fn decode_list<T>(
+    ty: &Type,
+    raw: &[u8],
+    decode: fn(&Type, &[u8]) -> DecodeResult<T>,
+) -> DecodeResult<Vec<Option<T>>> {
+    let elem = match ty.kind() {
+        Kind::Array(elem) => resolve_domain(elem),
+        _ => return Err(format!("{} is not an array type", ty.name()).into()),
+    };
+    Vec::<Option<Raw>>::from_sql(ty, raw)?
+        .into_iter()
+        .map(|v| v.map(|Raw(raw)| decode(elem, raw)).transpose())
+        .collect()
+}
+
+/// A `numeric` value decoded into a f64. Digits beyond the precision of f64 are lost.
+pub(crate) struct Numeric(pub f64);
+
+impl<'a> FromSql<'a> for Numeric {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> DecodeResult<Self> {
+        // The binary format is four u16 headers (ndigits, weight, sign, dscale)
+        // followed by `ndigits` base-10000 digits, the first one scaled by 10000^weight.
+        let word = |i: usize| u16::from_be_bytes([raw[2 * i], raw[2 * i + 1]]);
+        if raw.len() < 8 || raw.len() != 8 + 2 * word(0) as usize {
+            return Err("invalid message length for numeric".into());
+        }
+        let ndigits = word(0) as usize;
+        let weight = word(1) as i16 as i64;
+
+        let sign = match word(2) {
+            0x0000 => 1.0,
+            0x4000 => -1.0,
+            0xC000 => return Ok(Numeric(f64::NAN)),
+            0xD000 => return Ok(Numeric(f64::INFINITY)),
+            0xF000 => return Ok(Numeric(f64::NEG_INFINITY)),
+            s => return Err(format!("invalid sign {:#x} for numeric", s).into()),
+        };
+        if ndigits == 0 {
+            return Ok(Numeric(0.0));
+        }
+
+        // Let the float parser do the rounding so the result is the f64 closest to the decimal.
+        let mut digits = word(4).to_string();
+        for i in 1..ndigits {
+            digits.push_str(&format!("{:04}", word(4 + i)));
+        }
+        let exp = (weight - (ndigits as i64 - 1)) * 4;
+        let value: f64 = format!("{}e{}", digits, exp).parse()?;
+        Ok(Numeric(sign * value))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
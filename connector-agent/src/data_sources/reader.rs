@@ -0,0 +1,125 @@
+use super::csv::{CSVOptions, CSVSource, RejectFile};
+use super::SourceBuilder;
+use crate::data_order::DataOrder;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::types::DataType;
+use fehler::{throw, throws};
+use std::io::{self, Read};
+use std::sync::Arc;
+
+/// Opens the reader a query stands for.
+pub type Opener = Arc<dyn Fn(&str) -> Result<Box<dyn Read + Send>> + Send + Sync>;
+
+/// Box a factory of readers into an `Opener`.
+pub fn opener<F, R>(open: F) -> Opener
+where
+    F: Fn(&str) -> io::Result<R> + Send + Sync + 'static,
+    R: Read + Send + 'static,
+{
+    Arc::new(move |query| {
+        let reader = open(query).map_err(anyhow::Error::from)?;
+        Ok(Box::new(reader) as Box<dyn Read + Send>)
+    })
+}
+
+/// Reads the csv records of any reader, e.g. an in-memory buffer, stdin, a pipe or the body of
+/// an HTTP response, with the parsing of `CSVSource`.
+pub type ReaderSource = CSVSource;
+
+/// Builds `ReaderSource`s reading the readers a factory opens for the queries, so that the
+/// query of a partition only needs to name its input. The readers are decoded according to
+/// `CSVOptions::compression`, telling the compression from the query as if it were a path.
+///
+/// The factory is called again to count rows with `RowCount::Exact` or `RowCount::Estimate`,
+/// which a reader that can only be read once, such as stdin, does not allow.
+pub struct ReaderSourceBuilder {
+    options: CSVOptions,
+    batch_size: Option<usize>,
+    eager: bool,
+    schema: Option<Vec<DataType>>,
+    rejects: Option<Arc<RejectFile>>,
+    open: Opener,
+}
+
+impl ReaderSourceBuilder {
+    pub fn new<F, R>(open: F) -> Self
+    where
+        F: Fn(&str) -> io::Result<R> + Send + Sync + 'static,
+        R: Read + Send + 'static,
+    {
+        ReaderSourceBuilder {
+            options: CSVOptions::default(),
+            batch_size: None,
+            eager: false,
+            schema: None,
+            rejects: None,
+            open: opener(open),
+        }
+    }
+
+    /// Parse the readers with the dialect, spellings and error policy of `options`.
+    pub fn with_options(mut self, options: CSVOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read the records `batch_size` at a time, see `CSVSourceBuilder::with_batch_size`.
+    #[throws(ConnectorAgentError)]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Read every reader whole when its query runs, see `CSVSourceBuilder::with_eager`.
+    pub fn with_eager(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    /// The types of the columns, needed by `OnError::Skip`.
+    pub fn with_schema(mut self, schema: Vec<DataType>) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Append the rows skipped by `OnError::Skip` to the csv file at `path`, after the query and
+    /// line they are read from, see `CSVSourceBuilder::with_reject_file`.
+    pub fn with_reject_file(mut self, path: &str) -> Self {
+        self.rejects = Some(Arc::new(RejectFile::new(path)));
+        self
+    }
+
+    pub fn options(&self) -> &CSVOptions {
+        &self.options
+    }
+}
+
+impl SourceBuilder for ReaderSourceBuilder {
+    const DATA_ORDERS: &'static [DataOrder] = &[DataOrder::RowMajor];
+    type DataSource = ReaderSource;
+
+    #[throws(ConnectorAgentError)]
+    fn set_data_order(&mut self, data_order: DataOrder) {
+        if !matches!(data_order, DataOrder::RowMajor) {
+            throw!(ConnectorAgentError::UnsupportedDataOrder(data_order))
+        }
+    }
+
+    fn build(&mut self) -> Self::DataSource {
+        let mut source =
+            CSVSource::with_options(self.options.clone()).with_opener(self.open.clone());
+        if self.eager {
+            source = source.with_eager(true);
+        } else if let Some(batch_size) = self.batch_size {
+            source.batch_size = Some(batch_size);
+        }
+        if let Some(schema) = &self.schema {
+            source = source.with_schema(schema.clone());
+        }
+        source.rejects = self.rejects.clone();
+        source
+    }
+}
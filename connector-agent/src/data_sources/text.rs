@@ -0,0 +1,328 @@
+use super::csv::{CSVOptions, OnError};
+use crate::errors::{ConnectorAgentError, Result};
+use crate::types::DataType;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// The spellings of nulls and booleans in a text-based source. By default only the empty field is
+/// null, and booleans are spelled `true` and `false`. Single columns can be given spellings of
+/// their own, e.g. when one column uses `NA` for a null and `t` and `f` for booleans.
+#[derive(Clone, Debug)]
+pub struct TextOptions {
+    /// Fields read as null in nullable columns.
+    pub null_values: Vec<String>,
+    pub true_values: Vec<String>,
+    pub false_values: Vec<String>,
+    /// The spellings of single columns by index, used instead of these.
+    pub columns: HashMap<usize, TextOptions>,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            null_values: vec![String::new()],
+            true_values: vec!["true".to_string()],
+            false_values: vec!["false".to_string()],
+            columns: HashMap::new(),
+        }
+    }
+}
+
+impl TextOptions {
+    /// The spellings of Postgres `COPY ... CSV`: an empty field is null and booleans are
+    /// spelled `t` and `f`.
+    pub fn postgres() -> Self {
+        TextOptions::default().with_bool_values(&["t"], &["f"])
+    }
+
+    /// Read the fields in `values` as null.
+    pub fn with_null_values(mut self, values: &[&str]) -> Self {
+        self.null_values = values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    /// Read the fields in `true_values` and `false_values` as booleans.
+    pub fn with_bool_values(mut self, true_values: &[&str], false_values: &[&str]) -> Self {
+        self.true_values = true_values.iter().map(|v| v.to_string()).collect();
+        self.false_values = false_values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    /// Spell the values of column `col` as in `text` instead.
+    pub fn with_column(mut self, col: usize, text: TextOptions) -> Self {
+        self.columns.insert(col, text);
+        self
+    }
+
+    /// The spellings of column `col`.
+    pub fn column(&self, col: usize) -> &TextOptions {
+        self.columns.get(&col).unwrap_or(self)
+    }
+
+    pub fn is_null(&self, v: &[u8]) -> bool {
+        self.null_values.iter().any(|null| null.as_bytes() == v)
+    }
+
+    pub fn parse_bool(&self, v: &str) -> Option<bool> {
+        if self.true_values.iter().any(|t| t == v) {
+            Some(true)
+        } else if self.false_values.iter().any(|f| f == v) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// A type the fields of a text-based source are parsed as.
+pub(crate) trait FromText: Sized {
+    /// The name of the type in parse errors.
+    const NAME: &'static str;
+
+    /// Parse `v` as spelled according to `text`, which holds the spellings of its column.
+    fn from_text(v: &str, text: &TextOptions) -> Option<Self>;
+}
+
+macro_rules! impl_from_text_by_parse {
+    ($($t:ty),+) => {
+        $(
+            impl FromText for $t {
+                const NAME: &'static str = stringify!($t);
+
+                fn from_text(v: &str, _text: &TextOptions) -> Option<Self> {
+                    v.parse().ok()
+                }
+            }
+        )+
+    };
+}
+
+impl_from_text_by_parse!(u64, i64, f64, NaiveDate);
+
+impl FromText for bool {
+    const NAME: &'static str = "bool";
+
+    fn from_text(v: &str, text: &TextOptions) -> Option<Self> {
+        text.parse_bool(v)
+    }
+}
+
+impl FromText for String {
+    const NAME: &'static str = "String";
+
+    fn from_text(v: &str, _text: &TextOptions) -> Option<Self> {
+        Some(String::from(v))
+    }
+}
+
+impl FromText for NaiveDateTime {
+    const NAME: &'static str = "NaiveDateTime";
+
+    /// Accept both the ISO 8601 `T` separator and the space used by most databases, and read a
+    /// bare date as its midnight, the way columns mixing dates and timestamps are inferred.
+    fn from_text(v: &str, _text: &TextOptions) -> Option<Self> {
+        NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f")
+            .or_else(|_| v.parse())
+            .or_else(|_| v.parse::<NaiveDate>().map(|d| d.and_hms(0, 0, 0)))
+            .ok()
+    }
+}
+
+/// A csv source holding the fields of its batch as text, which it parses as the types of their
+/// columns when producing them, applying the null spellings and the `OnError` of its options.
+pub(crate) trait TextFields {
+    fn options(&self) -> &CSVOptions;
+
+    /// Advance to the next cell in row-major order and return its row and column in the batch.
+    fn next_cell(&mut self) -> Result<(usize, usize)>;
+
+    /// The bytes of a cell.
+    fn field(&self, cell: (usize, usize)) -> Cow<'_, [u8]>;
+
+    /// The error of a cell that does not parse as `T`, telling where it is read from.
+    fn parse_error<T: FromText>(&self, cell: (usize, usize)) -> ConnectorAgentError;
+
+    /// Parse a cell, or apply `OnError` if it does not parse: `None` stands for null.
+    fn parse<T: FromText>(&self, cell: (usize, usize)) -> Result<Option<T>> {
+        let text = self.options().text.column(cell.1);
+        let field = self.field(cell);
+        let parsed = std::str::from_utf8(&field)
+            .ok()
+            .and_then(|v| T::from_text(v, text));
+        match (parsed, self.options().on_error) {
+            (Some(v), _) => Ok(Some(v)),
+            (None, OnError::Null) | (None, OnError::Default) => Ok(None),
+            (None, _) => Err(self.parse_error::<T>(cell)),
+        }
+    }
+
+    fn produce_value<T: FromText + Default>(&mut self) -> Result<T> {
+        let cell = self.next_cell()?;
+        match self.parse(cell)? {
+            Some(v) => Ok(v),
+            None if self.options().on_error == OnError::Default => Ok(T::default()),
+            // the column is not nullable
+            None => Err(self.parse_error::<T>(cell)),
+        }
+    }
+
+    /// The null tokens of nullable columns are null.
+    fn produce_option<T: FromText>(&mut self) -> Result<Option<T>> {
+        let cell = self.next_cell()?;
+        if self
+            .options()
+            .text
+            .column(cell.1)
+            .is_null(&self.field(cell))
+        {
+            return Ok(None);
+        }
+        self.parse(cell)
+    }
+
+    /// Bytes other than the null tokens are read as they are, so they never fail to parse.
+    fn produce_bytes(&mut self) -> Result<Option<Vec<u8>>> {
+        let cell = self.next_cell()?;
+        let v = self.field(cell);
+        if self.options().text.column(cell.1).is_null(&v) {
+            return Ok(None);
+        }
+        Ok(Some(v.into_owned()))
+    }
+}
+
+/// Implement `Produce` for the types `TextFields` source `$source` parses its fields as.
+macro_rules! impl_produce_text {
+    ($source:ty) => {
+        impl_produce_text!(@value $source, u64, i64, f64, bool, String);
+        impl_produce_text!(
+            @option $source,
+            u64,
+            i64,
+            f64,
+            bool,
+            String,
+            chrono::NaiveDate,
+            chrono::NaiveDateTime
+        );
+
+        impl $crate::data_sources::Produce<Option<Vec<u8>>> for $source {
+            fn produce(&mut self) -> $crate::errors::Result<Option<Vec<u8>>> {
+                $crate::data_sources::text::TextFields::produce_bytes(self)
+            }
+        }
+    };
+    (@value $source:ty, $($t:ty),+) => {
+        $(
+            impl $crate::data_sources::Produce<$t> for $source {
+                fn produce(&mut self) -> $crate::errors::Result<$t> {
+                    $crate::data_sources::text::TextFields::produce_value(self)
+                }
+            }
+        )+
+    };
+    (@option $source:ty, $($t:ty),+) => {
+        $(
+            impl $crate::data_sources::Produce<Option<$t>> for $source {
+                fn produce(&mut self) -> $crate::errors::Result<Option<$t>> {
+                    $crate::data_sources::text::TextFields::produce_option(self)
+                }
+            }
+        )+
+    };
+}
+
+/// The kinds of values a column of a text-based source can hold, from the most specific.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    Int,
+    Float,
+    Date,
+    DateTime,
+    String,
+    /// Values that are not UTF-8.
+    Bytes,
+}
+
+impl Kind {
+    fn of(v: &[u8], text: &TextOptions) -> Kind {
+        let v = match std::str::from_utf8(v) {
+            Ok(v) => v,
+            Err(_) => return Kind::Bytes,
+        };
+        if text.parse_bool(v).is_some() {
+            Kind::Bool
+        } else if v.parse::<i64>().is_ok() {
+            Kind::Int
+        } else if v.parse::<f64>().is_ok() {
+            Kind::Float
+        } else if v.parse::<NaiveDate>().is_ok() {
+            Kind::Date
+        } else if NaiveDateTime::from_text(v, text).is_some() {
+            Kind::DateTime
+        } else {
+            Kind::String
+        }
+    }
+
+    /// The most specific kind holding the values of both kinds.
+    fn widen(self, other: Kind) -> Kind {
+        use Kind::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Int, Float) | (Float, Int) => Float,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            (Bytes, _) | (_, Bytes) => Bytes,
+            _ => String,
+        }
+    }
+}
+
+/// Infers the type of a column of a text-based source from a sample of its values.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TypeInference {
+    // `None` until a value other than a null is seen
+    kind: Option<Kind>,
+    nullable: bool,
+}
+
+impl TypeInference {
+    /// Account for the value `v` of the column, spelled according to `text`.
+    pub fn update(&mut self, v: &[u8], text: &TextOptions) {
+        if text.is_null(v) {
+            self.nullable = true;
+            return;
+        }
+        let kind = Kind::of(v, text);
+        self.kind = Some(self.kind.map_or(kind, |k| k.widen(kind)));
+    }
+
+    /// Account for the values another sample of the column has, e.g. of another partition.
+    pub fn merge(&mut self, other: &TypeInference) {
+        self.nullable |= other.nullable;
+        self.kind = match (self.kind, other.kind) {
+            (Some(a), Some(b)) => Some(a.widen(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// The type of the column. Columns of nulls only are nullable strings, and dates are
+    /// always nullable since there are no other date types.
+    pub fn data_type(&self) -> DataType {
+        match (self.kind, self.nullable) {
+            (Some(Kind::Bool), false) => DataType::Bool,
+            (Some(Kind::Bool), true) => DataType::OptBool,
+            (Some(Kind::Int), false) => DataType::I64,
+            (Some(Kind::Int), true) => DataType::OptI64,
+            (Some(Kind::Float), false) => DataType::F64,
+            (Some(Kind::Float), true) => DataType::OptF64,
+            (Some(Kind::Date), _) => DataType::OptDate,
+            (Some(Kind::DateTime), _) => DataType::OptDateTime,
+            (Some(Kind::String), false) => DataType::String,
+            (Some(Kind::String), true) | (None, _) => DataType::OptString,
+            (Some(Kind::Bytes), _) => DataType::OptBytes,
+        }
+    }
+}
@@ -1,12 +1,34 @@
 use crate::{
+    cancel::{CancellationToken, Timer},
     data_order::{coordinate, DataOrder},
     data_sources::{DataSource, SourceBuilder},
-    errors::Result,
-    types::{Transmit, TransmitChecked},
+    errors::{ConnectorAgentError, Result},
+    progress::{PartitionProgress, Phase, Progress},
+    row_count::RowCount,
+    semaphore::Semaphore,
+    split::SplitQuery,
+    types::{DataType, Transmit, TransmitChecked},
     typesystem::{Realize, TypeSystem},
-    writers::{PartitionWriter, Writer},
+    writers::{
+        arrow::{ArrowStreamPartitionWriter, ArrowStreamWriter, RecordBatchStream},
+        PartitionWriter, Writer,
+    },
 };
+use fehler::{throw, throws};
 use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Rows written at a time, between progress reports and checks for cancellation, when written
+/// in row-major order.
+const STEP_ROWS: usize = 4096;
+
+/// Spare partitions per thread of the pool, which the rows the threads steal from running
+/// partitions are written to.
+const STEALS_PER_THREAD: usize = 4;
 
 /// A dispatcher owns a `SourceBuilder` `SB` and a vector of `queries`
 /// `schema` is a temporary input before we implement infer schema or get schema from DB.
@@ -14,12 +36,18 @@ pub struct Dispatcher<SB, WT, TS> {
     source_builder: SB,
     writer: WT,
     schema: Vec<TS>,
-    queries: Vec<String>,
+    queries: Vec<Box<dyn SplitQuery>>,
+    row_count: RowCount,
+    progress: Option<Box<dyn Progress>>,
+    cancellation: CancellationToken,
+    timeout: Option<Duration>,
+    thread_pool: Option<Arc<ThreadPool>>,
+    max_concurrency: Option<usize>,
 }
 
 impl<SB, WT, TS> Dispatcher<SB, WT, TS>
 where
-    SB: SourceBuilder,
+    SB: SourceBuilder + Send,
     SB::DataSource: Send,
     TS: TypeSystem,
     WT: for<'a> Writer<'a, TypeSystem = TS>,
@@ -29,12 +57,77 @@ where
     /// Create a new dispatcher by providing a source builder, schema (temporary) and the queries
     /// to be issued to the data source.
     pub fn new(source_builder: SB, writer: WT, schema: Vec<TS>, queries: Vec<String>) -> Self {
+        Self::new_split(source_builder, writer, schema, queries)
+    }
+
+    /// Like `new`, but the queries may be split into smaller partitions before and while they
+    /// run, see `SplitQuery`. The rows that threads steal from running partitions are written to
+    /// partitions after those of all the queries, so the rows of a query are not kept in order.
+    pub fn new_split<Q>(source_builder: SB, writer: WT, schema: Vec<TS>, queries: Vec<Q>) -> Self
+    where
+        Q: SplitQuery + 'static,
+    {
         Dispatcher {
             source_builder,
             writer,
             schema,
-            queries,
+            queries: queries
+                .into_iter()
+                .map(|query| Box::new(query) as Box<dyn SplitQuery>)
+                .collect(),
+            row_count: RowCount::default(),
+            progress: None,
+            cancellation: CancellationToken::new(),
+            timeout: None,
+            thread_pool: None,
+            max_concurrency: None,
+        }
+    }
+
+    /// Choose how the number of rows of each query is found out before allocating the writer.
+    /// By default all queries are run first and the rows they return are counted.
+    pub fn with_row_count(mut self, row_count: RowCount) -> Self {
+        self.row_count = row_count;
+        self
+    }
+
+    /// Report the progress of every partition to `progress` while running.
+    pub fn with_progress<P: Progress + 'static>(mut self, progress: P) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Stop the run with `ConnectorAgentError::Cancelled` once `token` is cancelled. The sources
+    /// are handed the token too, so that they can interrupt the queries they are running.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Cancel the run if it takes longer than `timeout`, failing with
+    /// `ConnectorAgentError::Timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Process the partitions on `pool` instead of the global rayon pool, e.g. to keep slow
+    /// queries from occupying the threads other parts of the program use.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Run at most `n` sources at the same time, however many partitions there are. The other
+    /// partitions wait for a source to finish, so that there can be more partitions than
+    /// connections to the database and the work is still balanced across them.
+    #[throws(ConnectorAgentError)]
+    pub fn with_max_concurrency(mut self, n: usize) -> Self {
+        if n == 0 {
+            throw!(ConnectorAgentError::NotPositive("concurrency"));
         }
+        self.max_concurrency = Some(n);
+        self
     }
 
     pub fn run_checked(self) -> Result<WT> {
@@ -48,19 +141,126 @@ where
     /// Run the dispatcher by specifying the writer, the dispatcher will fetch, parse the data
     /// and return a writer with parsed result
     fn entry(mut self, checked: bool) -> Result<WT> {
+        let cancellation = self.cancellation.clone();
+        let timer = self
+            .timeout
+            .map(|timeout| Timer::start(timeout, move || cancellation.cancel()));
+
+        match self.dispatch(checked) {
+            Ok(()) => Ok(self.writer),
+            Err(ConnectorAgentError::Cancelled) => match timer {
+                Some(timer) if timer.fired() => Err(ConnectorAgentError::Timeout(timer.timeout())),
+                _ => Err(ConnectorAgentError::Cancelled),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn dispatch(&mut self, checked: bool) -> Result<()> {
         let dorder = coordinate(SB::DATA_ORDERS, WT::DATA_ORDERS)?;
         self.source_builder.set_data_order(dorder)?;
 
-        // generate sources
-        let mut sources: Vec<SB::DataSource> = (0..self.queries.len())
-            .map(|_i| self.source_builder.build())
+        let pool = self.thread_pool.as_deref();
+        let limit = self.max_concurrency.map(Semaphore::new);
+        let acquire = || limit.as_ref().map(|limit| limit.acquire());
+
+        // split the queries before any of them runs, so that a partition keeps its index from
+        // planning to transfer, and every report names the query the partition is a part of
+        let parts: Vec<Part> = std::mem::take(&mut self.queries)
+            .into_iter()
+            .enumerate()
+            .map(|(index, query)| Part { index, query })
             .collect();
+        let parts: Vec<Part> = install(pool, || {
+            rayon::iter::split(parts, split_parts)
+                .flat_map_iter(|parts| parts)
+                .collect()
+        });
+        let query_of: Vec<usize> = parts.iter().map(|part| part.index).collect();
+        let nparts = parts.len();
+
+        let progress = self.progress.as_deref();
+        let report_at = |partition: usize, query: usize, phase, rows, total, bytes| {
+            report(progress, || PartitionProgress {
+                partition,
+                query,
+                phase,
+                rows,
+                total,
+                bytes,
+            })
+        };
+
+        let row_count = self.source_builder.row_count(self.row_count);
+        let cancellation = &self.cancellation;
+        let source_builder = Mutex::new(&mut self.source_builder);
+        let build = || {
+            let mut source = source_builder.lock().unwrap().build();
+            source.set_cancellation_token(cancellation.clone());
+            source
+        };
 
-        // run queries
-        sources
-            .par_iter_mut()
-            .zip_eq(self.queries.as_slice())
-            .for_each(|(source, query)| source.run_query(query.as_str()).expect("run query"));
+        // plan the partitions: build a source for every part, claim the first chunk of the part
+        // and count its rows, running the chunk up front only if counting needs it
+        let planned: Vec<(Option<String>, Option<Part>, SB::DataSource, usize)> =
+            install(pool, || {
+                parts
+                    .into_par_iter()
+                    .enumerate()
+                    .map(|(i, part)| {
+                        let _permit = acquire();
+                        cancellation.check()?;
+                        let query = part.index;
+                        let whole = part.query.query();
+                        let mut rest = Some(part);
+                        let mut next = claim(&mut rest);
+                        let mut source = build();
+                        let nrows = match row_count {
+                            RowCount::Query => {
+                                report_at(i, query, Phase::Query, 0, None, 0);
+                                if let Some(chunk) = next.take() {
+                                    source.run_query(chunk.as_str())?;
+                                }
+                                source.nrows()
+                            }
+                            RowCount::Exact | RowCount::Estimate => {
+                                report_at(i, query, Phase::Count, 0, None, 0);
+                                source.count_rows(whole.as_str(), row_count)?
+                            }
+                            RowCount::Unknown => 0,
+                        };
+                        Ok((next, rest, source, nrows))
+                    })
+                    .collect::<Result<_>>()
+            })?;
+        let mut nexts = Vec::with_capacity(nparts);
+        let mut rests = Vec::with_capacity(nparts);
+        let mut sources = Vec::with_capacity(nparts);
+        let mut num_rows = Vec::with_capacity(nparts);
+        for (next, rest, source, nrows) in planned {
+            nexts.push(next);
+            rests.push(rest);
+            sources.push(source);
+            num_rows.push(nrows);
+        }
+        let totals: Vec<Option<usize>> = match row_count {
+            RowCount::Unknown => vec![None; nparts],
+            _ => num_rows.iter().map(|&n| Some(n)).collect(),
+        };
+
+        // the parts left to claim can be stolen by the threads that are done, into spare
+        // partitions after the others, which hold no rows unless something is stolen
+        let nspares = if rests.iter().any(Option::is_some) {
+            install(pool, rayon::current_num_threads) * STEALS_PER_THREAD
+        } else {
+            0
+        };
+        let rests: Vec<Mutex<Option<Part>>> = rests
+            .into_iter()
+            .chain((0..nspares).map(|_| None))
+            .map(Mutex::new)
+            .collect();
+        num_rows.resize(nparts + nspares, 0);
 
         // infer schema if not given
         // let self.schema = sources[0].infer_schema();
@@ -79,35 +279,232 @@ where
             .collect();
 
         // allocate memory and create one partition writer for each source
-        let num_rows: Vec<usize> = sources.iter().map(|source| source.nrows()).collect();
         self.writer
             .allocate(num_rows.iter().sum(), self.schema.clone(), dorder)?;
+        for (i, source) in sources.iter().enumerate() {
+            report_at(
+                i,
+                query_of[i],
+                Phase::Allocate,
+                0,
+                totals[i],
+                source.nbytes(),
+            );
+        }
+        let mut writers = self.writer.partition_writers(num_rows.as_slice());
+        let spares = Mutex::new(
+            writers
+                .split_off(nparts)
+                .into_iter()
+                .enumerate()
+                .map(|(j, writer)| (nparts + j, writer))
+                .rev()
+                .collect::<Vec<_>>(),
+        );
 
-        // parse and write
-        self.writer
-            .partition_writers(num_rows.as_slice())
-            .into_par_iter()
-            .zip_eq(sources)
-            .for_each(|(mut writer, mut source)| {
-                let f = funcs.clone();
-                match dorder {
-                    DataOrder::RowMajor => {
-                        for row in 0..writer.nrows() {
-                            for col in 0..writer.ncols() {
-                                f[col](&mut source, &mut writer, row, col).expect("write record");
+        // steal the second half of what a running partition has not claimed yet
+        let steal = || {
+            let mut spares = spares.lock().unwrap();
+            if spares.is_empty() {
+                return None;
+            }
+            let part = rests
+                .iter()
+                .find_map(|rest| rest.lock().unwrap().as_mut().and_then(Part::split))?;
+            let (j, writer) = spares.pop().unwrap();
+            Some((j, part, writer))
+        };
+
+        // parse and write the chunks of every part, resizing the partition writers whose row
+        // count was not exact, whose sources fetch in batches or whose parts were stolen from,
+        // and once done steal a part of a partition still running
+        install(pool, || {
+            writers
+                .into_par_iter()
+                .zip_eq(sources)
+                .zip_eq(nexts)
+                .enumerate()
+                .try_for_each(|(i, ((writer, source), next))| -> Result<()> {
+                    let _permit = acquire();
+                    let mut partition = Some((i, query_of[i], totals[i], writer, source, next));
+                    while let Some((i, query, total, mut writer, mut source, mut next)) =
+                        partition.take()
+                    {
+                        cancellation.check()?;
+
+                        // rows of a batch can only be written in steps if they are written row
+                        // by row
+                        let step = match dorder {
+                            DataOrder::RowMajor => STEP_ROWS,
+                            DataOrder::ColumnMajor => usize::MAX,
+                        };
+                        let transferred = |rows, source: &SB::DataSource| {
+                            report_at(i, query, Phase::Transfer, rows, total, source.nbytes())
+                        };
+
+                        let f = funcs.clone();
+                        let mut start = 0;
+                        let mut n = source.nrows();
+                        loop {
+                            while n > 0 {
+                                let end = start + n;
+                                if end > writer.nrows() {
+                                    writer.grow(end - writer.nrows())?;
+                                }
+                                while start < end {
+                                    let next = end.min(start.saturating_add(step));
+                                    write_rows(&f, &mut source, &mut writer, start..next, dorder)?;
+                                    start = next;
+                                    transferred(start, &source);
+                                    cancellation.check()?;
+                                }
+                                n = source.fetch_next()?;
                             }
+
+                            let chunk = match next.take() {
+                                Some(chunk) => chunk,
+                                None => match claim(&mut rests[i].lock().unwrap()) {
+                                    Some(chunk) => chunk,
+                                    None => break,
+                                },
+                            };
+                            report_at(i, query, Phase::Query, start, total, source.nbytes());
+                            source.run_query(chunk.as_str())?;
+                            n = source.nrows();
                         }
-                    }
-                    DataOrder::ColumnMajor => {
-                        for col in 0..writer.ncols() {
-                            for row in 0..writer.nrows() {
-                                f[col](&mut source, &mut writer, row, col).expect("write record");
-                            }
+                        if start == 0 {
+                            transferred(0, &source);
+                        }
+                        if start < writer.nrows() {
+                            writer.shrink(start)?;
                         }
+                        writer.finalize()?;
+
+                        partition = steal().map(|(j, part, writer)| {
+                            let query = part.index;
+                            *rests[j].lock().unwrap() = Some(part);
+                            (j, query, None, writer, build(), None)
+                        });
                     }
-                }
-            });
+                    Ok(())
+                })
+        })?;
+        for (_, mut writer) in spares.into_inner().unwrap() {
+            writer.finalize()?;
+        }
+        self.writer.finalize()
+    }
+}
+
+impl<SB> Dispatcher<SB, ArrowStreamWriter, DataType>
+where
+    SB: SourceBuilder + Send + 'static,
+    SB::DataSource: Send,
+    DataType: for<'a> Realize<Transmit<'a, SB::DataSource, ArrowStreamPartitionWriter<'a>>>
+        + for<'a> Realize<TransmitChecked<'a, SB::DataSource, ArrowStreamPartitionWriter<'a>>>,
+{
+    /// Run the dispatcher in a background thread and return the record batches as the
+    /// partitions produce them. Values are written checked, so that once the stream is dropped the
+    /// partitions stop as soon as they fail to send their next batch, if they are not cancelled
+    /// before. See `ArrowStreamWriter` for which row counts keep the memory bounded.
+    pub fn run_stream(mut self) -> RecordBatchStream {
+        let receiver = self
+            .writer
+            .take_receiver()
+            .expect("the receiver of the writer is taken");
+        let cancellation = self.cancellation.clone();
+        let handle = thread::spawn(move || self.run_checked().map(|_| ()));
+        RecordBatchStream::new(receiver, handle, cancellation)
+    }
+}
+
+/// A part of the query at `index`.
+struct Part {
+    index: usize,
+    query: Box<dyn SplitQuery>,
+}
+
+impl Part {
+    /// Split off the second half of the part, see `SplitQuery::split`.
+    fn split(&mut self) -> Option<Part> {
+        let index = self.index;
+        self.query.split().map(|query| Part { index, query })
+    }
+}
 
-        Ok(self.writer)
+/// Halve a list of parts, and once a single part is left, split the part itself.
+fn split_parts(mut parts: Vec<Part>) -> (Vec<Part>, Option<Vec<Part>>) {
+    if parts.len() > 1 {
+        let second = parts.split_off(parts.len() / 2);
+        return (parts, Some(second));
+    }
+    let second = parts
+        .first_mut()
+        .and_then(Part::split)
+        .map(|part| vec![part]);
+    (parts, second)
+}
+
+/// Claim the query of the next chunk of what a partition has not claimed of its part yet, which
+/// is all of it if the part does not split off chunks.
+fn claim(rest: &mut Option<Part>) -> Option<String> {
+    match rest.as_mut().and_then(|part| part.query.split_front()) {
+        Some(chunk) => Some(chunk.query()),
+        None => rest.take().map(|part| part.query.query()),
+    }
+}
+
+/// Run `op` on `pool` if there is one, otherwise in the current thread, whose parallel iterators
+/// then run on the global pool.
+fn install<R, OP>(pool: Option<&ThreadPool>, op: OP) -> R
+where
+    R: Send,
+    OP: FnOnce() -> R + Send,
+{
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+/// Report the progress built by `progress_of` if there is anyone to report to.
+fn report<F>(progress: Option<&dyn Progress>, progress_of: F)
+where
+    F: FnOnce() -> PartitionProgress,
+{
+    if let Some(progress) = progress {
+        progress.report(&progress_of());
+    }
+}
+
+type TransmitFn<S, W> = fn(&mut S, &mut W, usize, usize) -> Result<()>;
+
+/// Transmit the `rows` of `writer` out of `source`.
+fn write_rows<'a, S, W>(
+    funcs: &[TransmitFn<S, W>],
+    source: &mut S,
+    writer: &mut W,
+    rows: Range<usize>,
+    dorder: DataOrder,
+) -> Result<()>
+where
+    W: PartitionWriter<'a>,
+{
+    match dorder {
+        DataOrder::RowMajor => {
+            for row in rows {
+                for col in 0..writer.ncols() {
+                    funcs[col](source, writer, row, col)?;
+                }
+            }
+        }
+        DataOrder::ColumnMajor => {
+            for col in 0..writer.ncols() {
+                for row in rows.clone() {
+                    funcs[col](source, writer, row, col)?;
+                }
+            }
+        }
     }
+    Ok(())
 }
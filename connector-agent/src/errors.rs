@@ -1,4 +1,5 @@
-use crate::{data_order::DataOrder, types::DataType};
+use crate::{data_order::DataOrder, row_count::RowCount, types::DataType};
+use std::time::Duration;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, ConnectorAgentError>;
@@ -19,6 +20,87 @@ pub enum ConnectorAgentError {
     #[error("Cannot resolve data order: got {0:?} from source, {1:?} from destination.")]
     CannotResolveDataOrder(Vec<DataOrder>, Vec<DataOrder>),
 
+    /// The writer has a fixed size and cannot take more rows than it allocated.
+    #[error("Writer cannot grow beyond the allocated rows.")]
+    CannotGrow,
+
+    /// The writer has a fixed size and cannot drop rows it allocated but never wrote.
+    #[error("Writer cannot shrink below the allocated rows.")]
+    CannotShrink,
+
+    /// The receiving end of a streaming writer was dropped before all the batches were sent.
+    #[error("The receiver of the stream is closed.")]
+    StreamClosed,
+
+    /// The run was cancelled through its `CancellationToken`.
+    #[error("The run is cancelled.")]
+    Cancelled,
+
+    /// The run, or one of its queries, took longer than the timeout given.
+    #[error("Timed out after {0:?}.")]
+    Timeout(Duration),
+
+    /// A batch of rows, e.g. fetched from a cursor or sent down a stream, was given no rows.
+    #[error("The batch size must be positive.")]
+    ZeroBatchSize,
+
+    /// A count or size that must be positive, e.g. the number of connections, was given zero.
+    #[error("The {0} must be positive.")]
+    NotPositive(&'static str),
+
+    /// A run was given an empty list of queries, from which nothing can be described.
+    #[error("No query given.")]
+    NoQuery,
+
+    #[error("Row count {0:?} not supported by the source.")]
+    UnsupportedRowCount(RowCount),
+
+    /// The Postgres type of a result column has no corresponding `DataType`.
+    #[error("Postgres type {0} (oid {1}) is not supported.")]
+    UnsupportedPostgresType(String, u32),
+
+    #[error(transparent)]
+    PostgresError(#[from] postgres::Error),
+
+    #[error(transparent)]
+    PostgresPoolError(#[from] r2d2::Error),
+
+    #[error(transparent)]
+    CSVError(#[from] csv::Error),
+
+    /// A value of a csv file does not parse as the type of its column.
+    #[error("Cannot parse {text:?} as {ty} at line {line}, column {col} of {path}.")]
+    CSVParseError {
+        path: String,
+        line: u64,
+        col: usize,
+        text: String,
+        ty: &'static str,
+    },
+
+    /// A line of a JSON file is not a JSON object.
+    #[error("Invalid JSON at line {line} of {path}: {source}")]
+    JsonError {
+        path: String,
+        line: u64,
+        source: serde_json::Error,
+    },
+
+    /// A value of a JSON file does not have the type of its column, or is null in a column that
+    /// is not nullable.
+    #[error("Cannot read {value} as {ty} at line {line}, field {field:?} of {path}.")]
+    JsonValueError {
+        path: String,
+        line: u64,
+        field: String,
+        value: String,
+        ty: &'static str,
+    },
+
+    /// A column of a JSON source is not a valid path of the objects.
+    #[error("Invalid field path {path:?}: {reason}.")]
+    FieldPathError { path: String, reason: &'static str },
+
     /// Any other errors that are too trivial to be put here explicitly.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -5,22 +5,37 @@ pub mod s3;
 #[macro_use]
 mod typesystem;
 mod any_array;
+mod cancel;
 mod data_order;
 pub mod data_sources;
 mod dispatcher;
 mod errors;
+mod progress;
+mod row_count;
+mod semaphore;
+mod split;
 mod types;
 pub mod writers;
 
 pub use crate::any_array::{AnyArray, AnyArrayView, AnyArrayViewMut};
+pub use crate::cancel::{CancelGuard, CancellationToken};
 pub use crate::data_order::DataOrder;
 pub use crate::data_sources::{
-    csv::{CSVSource, CSVSourceBuilder},
+    compression::Compression,
+    csv::{ByteRangeQuery, CSVOptions, CSVSource, CSVSourceBuilder, OnError},
+    json::{JsonFormat, JsonSource, JsonSourceBuilder, ValueMap},
     mixed::{MixedSource, MixedSourceBuilder},
+    mmap::{MmapCSVSource, MmapCSVSourceBuilder},
+    postgres::{PostgresSource, PostgresSourceBuilder},
+    reader::{ReaderSource, ReaderSourceBuilder},
+    text::TextOptions,
     {DataSource, SourceBuilder},
 };
 pub use crate::dispatcher::Dispatcher;
 pub use crate::errors::{ConnectorAgentError, Result};
+pub use crate::progress::{PartitionProgress, Phase, Progress};
+pub use crate::row_count::RowCount;
+pub use crate::split::{RangeQuery, SplitQuery};
 pub use crate::types::DataType;
 pub use crate::typesystem::{ParameterizedFunc, ParameterizedOn, Realize, TypeAssoc, TypeSystem};
 pub use crate::writers::{PartitionWriter, Writer};
@@ -1,3 +1,8 @@
+use crate::{
+    writers::arrow::{ArrowStreamWriter, ArrowWriter, RecordBatchStream},
+    CancellationToken, ConnectorAgentError, Dispatcher, PartitionProgress, PostgresSourceBuilder,
+    RowCount,
+};
 use arrow::csv::reader::ReaderBuilder;
 use arrow::datatypes::{Schema, SchemaRef};
 use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
@@ -10,18 +15,56 @@ use serde_json::{from_str, Value};
 use std::collections::HashMap;
 use std::io::{Cursor, Read};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::task::spawn_blocking;
 
+const CONN: &str = "host=localhost user=postgres dbname=tpch port=6666 password=postgres";
+
+type Table = HashMap<String, Vec<(*const FFI_ArrowArray, *const FFI_ArrowSchema)>>;
+
+/// A callback receiving the progress of the dispatcher.
+pub type ProgressFn = Box<dyn Fn(&PartitionProgress) + Send + Sync>;
+
+/// How the `Dispatcher` reading the queries is run.
+#[derive(Default)]
+pub struct RunOptions {
+    pub progress: Option<ProgressFn>,
+    /// Cancel the whole run after this long.
+    pub timeout: Option<Duration>,
+    /// Cancel every query that runs longer than this.
+    pub query_timeout: Option<Duration>,
+    /// Run at most this many queries at the same time, through as many connections.
+    pub max_concurrency: Option<usize>,
+    pub cancellation: CancellationToken,
+}
+
+impl RunOptions {
+    /// The number of connections needed for `nqueries` queries.
+    fn nconn(&self, nqueries: usize) -> usize {
+        self.max_concurrency.map_or(nqueries, |n| n.min(nqueries))
+    }
+}
+
+/// Read the results of `sqls` as arrow arrays. If `schema` (an arrow schema in JSON) is not given,
+/// it is derived from the statement description of the first query, and the results are read by
+/// the `Dispatcher`, which is run according to `options`.
 #[throws(Error)]
-pub async fn read_pg<S>(
-    sqls: &[S],
-    schema: &str,
-) -> HashMap<String, Vec<(*const FFI_ArrowArray, *const FFI_ArrowSchema)>>
+pub async fn read_pg<S>(sqls: &[S], schema: Option<&str>, options: RunOptions) -> Table
 where
     S: AsRef<str>,
 {
-    let schema = Arc::new(Schema::from(&from_str::<Value>(schema)?)?);
+    let schema = match schema {
+        Some(schema) => Arc::new(Schema::from(&from_str::<Value>(schema)?)?),
+        None => {
+            let sqls: Vec<String> = sqls.iter().map(|s| s.as_ref().to_string()).collect();
+            let batches = spawn_blocking(move || read_sql_with_dispatcher(sqls, options)).await??;
+            let mut table = HashMap::new();
+            for batch in batches {
+                add_to_table(&mut table, &batch)?;
+            }
+            return table;
+        }
+    };
     let mut futs: FuturesOrdered<_> = sqls
         .iter()
         .map(|sql| read_sql_as_batch(sql, schema.clone()))
@@ -32,19 +75,7 @@ where
     while let Some(rb) = futs.next().await {
         if let Some(batches) = rb? {
             for batch in batches {
-                for (i, f) in batch.schema().fields().iter().enumerate() {
-                    use arrow::datatypes::DataType::*;
-                    match f.data_type() {
-                        Null | Boolean | Int8 | Int16 | Int32 | Int64 | UInt8 | UInt16 | UInt32
-                        | UInt64 | Float16 | Float32 | Float64 | Utf8 | LargeUtf8 | Binary
-                        | LargeBinary => {}
-                        _ => continue,
-                    }
-                    table
-                        .entry(f.name().clone())
-                        .or_insert_with(|| vec![])
-                        .push(batch.column(i).to_raw()?)
-                }
+                add_to_table(&mut table, &batch)?;
             }
         }
     }
@@ -52,6 +83,84 @@ where
     table
 }
 
+#[throws(Error)]
+fn add_to_table(table: &mut Table, batch: &RecordBatch) {
+    for (i, f) in batch.schema().fields().iter().enumerate() {
+        use arrow::datatypes::DataType::*;
+        match f.data_type() {
+            Null | Boolean | Int8 | Int16 | Int32 | Int64 | UInt8 | UInt16 | UInt32 | UInt64
+            | Float16 | Float32 | Float64 | Utf8 | LargeUtf8 | Binary | LargeBinary => {}
+            _ => continue,
+        }
+        table
+            .entry(f.name().clone())
+            .or_insert_with(|| vec![])
+            .push(batch.column(i).to_raw()?)
+    }
+}
+
+/// Run `sqls` through the `Dispatcher` with a `PostgresSource`, typing the columns
+/// by preparing the first query.
+#[throws(Error)]
+fn read_sql_with_dispatcher(sqls: Vec<String>, options: RunOptions) -> Vec<RecordBatch> {
+    let first = first_query(&sqls)?;
+    let mut builder = PostgresSourceBuilder::new(CONN, options.nconn(sqls.len()))?;
+    if let Some(timeout) = options.query_timeout {
+        builder = builder.with_query_timeout(timeout);
+    }
+    let (names, schema) = builder.describe(first)?;
+
+    let mut dispatcher = Dispatcher::new(builder, ArrowWriter::new(), schema, sqls)
+        .with_cancellation(options.cancellation);
+    if let Some(progress) = options.progress {
+        dispatcher = dispatcher.with_progress(progress);
+    }
+    if let Some(timeout) = options.timeout {
+        dispatcher = dispatcher.with_timeout(timeout);
+    }
+    if let Some(n) = options.max_concurrency {
+        dispatcher = dispatcher.with_max_concurrency(n)?;
+    }
+    dispatcher.run()?.finish(names)
+}
+
+/// Stream the results of `sqls` as record batches of at most `batch_size` rows. Each query is
+/// read through a cursor and only a few batches are buffered, so memory stays bounded however
+/// slowly the batches are consumed. Dropping the stream stops the queries still running.
+#[throws(Error)]
+pub fn iter_sql(sqls: Vec<String>, batch_size: usize, options: RunOptions) -> RecordBatchStream {
+    let first = first_query(&sqls)?;
+    let mut builder =
+        PostgresSourceBuilder::new(CONN, options.nconn(sqls.len()))?.with_cursor(batch_size)?;
+    if let Some(timeout) = options.query_timeout {
+        builder = builder.with_query_timeout(timeout);
+    }
+    let (names, schema) = builder.describe(first)?;
+
+    let writer = ArrowStreamWriter::new(names, batch_size, sqls.len())?;
+    let mut dispatcher = Dispatcher::new(builder, writer, schema, sqls)
+        .with_row_count(RowCount::Unknown)
+        .with_cancellation(options.cancellation);
+    if let Some(progress) = options.progress {
+        dispatcher = dispatcher.with_progress(progress);
+    }
+    if let Some(timeout) = options.timeout {
+        dispatcher = dispatcher.with_timeout(timeout);
+    }
+    if let Some(n) = options.max_concurrency {
+        dispatcher = dispatcher.with_max_concurrency(n)?;
+    }
+    dispatcher.run_stream()
+}
+
+/// The query the schema is described from. Checked before the connection pool is sized by the
+/// number of queries, which cannot be zero.
+fn first_query(sqls: &[String]) -> Result<&str, ConnectorAgentError> {
+    sqls.first()
+        .map(String::as_str)
+        .ok_or(ConnectorAgentError::NoQuery)
+}
+
 #[throws(Error)]
 pub async fn read_sql_as_batch<S>(sql: &S, schema: SchemaRef) -> Option<Vec<RecordBatch>>
 where
@@ -62,10 +171,7 @@ where
     let batches = spawn_blocking(move || -> Result<_, Error> {
         let start = Instant::now();
         let mut buf = vec![];
-        let mut client = Client::connect(
-            "host=localhost user=postgres dbname=tpch port=6666 password=postgres",
-            NoTls,
-        )?;
+        let mut client = Client::connect(CONN, NoTls)?;
         client.copy_out(&*query)?.read_to_end(&mut buf)?;
         let t_copy = start.elapsed();
         // println!("copy: {:?}", t_copy);
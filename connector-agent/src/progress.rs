@@ -0,0 +1,54 @@
+/// The phase a partition is in when its progress is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// The rows of the partition are counted, for row counts other than `RowCount::Query`.
+    Count,
+    /// The query of the partition is run.
+    Query,
+    /// The writer is allocated for the rows counted.
+    Allocate,
+    /// The rows of the partition are written.
+    Transfer,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Count => "count",
+            Phase::Query => "query",
+            Phase::Allocate => "allocate",
+            Phase::Transfer => "transfer",
+        }
+    }
+}
+
+/// The progress of one partition of the dispatcher.
+#[derive(Clone, Copy, Debug)]
+pub struct PartitionProgress {
+    /// The index of the partition, which is a part of a query if the queries are split.
+    pub partition: usize,
+    /// The index of the query the partition is a part of.
+    pub query: usize,
+    pub phase: Phase,
+    /// Rows written so far.
+    pub rows: usize,
+    /// Rows the partition is expected to have, unless the row count is `RowCount::Unknown`.
+    pub total: Option<usize>,
+    /// Bytes read from the source so far, see `DataSource::nbytes`.
+    pub bytes: usize,
+}
+
+/// Receives the progress of a dispatcher. It is called from the threads writing the partitions,
+/// so it should return quickly. Closures taking a `&PartitionProgress` implement it.
+pub trait Progress: Send + Sync {
+    fn report(&self, progress: &PartitionProgress);
+}
+
+impl<F> Progress for F
+where
+    F: Fn(&PartitionProgress) + Send + Sync,
+{
+    fn report(&self, progress: &PartitionProgress) {
+        self(progress)
+    }
+}
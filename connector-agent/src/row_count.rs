@@ -0,0 +1,21 @@
+/// How the `Dispatcher` learns the number of rows of each partition, to allocate the writer
+/// before writing into it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RowCount {
+    /// Run all the queries before allocating and take the number of rows each source fetched.
+    /// Sources need to buffer their whole result for the count to be complete.
+    Query,
+    /// Ask the sources for the exact number of rows of each query, e.g. by `COUNT(*)`.
+    Exact,
+    /// Ask the sources for an estimate of the number of rows of each query, e.g. from the
+    /// query planner. The writer needs to grow or shrink if the estimate is off.
+    Estimate,
+    /// Allocate no rows and grow the writer as the rows arrive.
+    Unknown,
+}
+
+impl Default for RowCount {
+    fn default() -> Self {
+        RowCount::Query
+    }
+}
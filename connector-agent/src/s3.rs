@@ -1,3 +1,5 @@
+pub use crate::data_sources::json::JsonFormat;
+use crate::data_sources::json::{write_jsonl, Columns, ValueMap};
 use arrow::datatypes::{Schema, SchemaRef};
 use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
 use arrow::json::reader::ReaderBuilder;
@@ -13,22 +15,22 @@ use serde_json::{from_str, Value};
 use std::collections::HashMap;
 use std::io::{Cursor, Read};
 use std::sync::Arc;
-use strum::EnumString;
 use tokio::io::AsyncReadExt;
 use tokio::task::spawn_blocking;
 
-#[derive(Debug, Clone, Copy, EnumString)]
-pub enum JsonFormat {
-    JsonL,
-    Array,
-}
-
+/// Read the gzipped JSON `objects` of `bucket` into arrow arrays of `schema`, after replacing the
+/// values listed in `value_map`, given as the JSON of a `ValueMap`. The names of the fields of
+/// `schema` are the names of the fields of the objects, read as they are. If `columns` is given,
+/// the objects are flattened into the columns at these paths instead, e.g. `asks[*][1]`, as
+/// `Columns` describes, and the fields of `schema` are named after the paths.
 #[throws(Error)]
 pub async fn read_s3<S>(
     bucket: &str,
     objects: &[S],
     schema: &str,
     json_format: JsonFormat,
+    value_map: Option<&str>,
+    columns: Option<&[S]>,
 ) -> HashMap<String, Vec<(*const FFI_ArrowArray, *const FFI_ArrowSchema)>>
 where
     S: AsRef<str>,
@@ -36,6 +38,16 @@ where
     let client = S3Client::new(Region::UsWest2);
 
     let schema = Arc::new(Schema::from(&from_str::<Value>(schema)?)?);
+    let value_map: Arc<ValueMap> = Arc::new(match value_map {
+        Some(value_map) => from_str(value_map)?,
+        None => ValueMap::default(),
+    });
+    let columns = match columns {
+        Some(columns) => Some(Columns::new(columns)?)
+            .filter(|columns| !columns.is_flat())
+            .map(Arc::new),
+        None => None,
+    };
     let mut futs: FuturesOrdered<_> = objects
         .iter()
         .map(|obj| {
@@ -46,7 +58,15 @@ where
                     ..Default::default()
                 })
                 .err_into()
-                .and_then(|resp| read_as_record_batch(resp, schema.clone(), json_format))
+                .and_then(|resp| {
+                    read_as_record_batch(
+                        resp,
+                        schema.clone(),
+                        json_format,
+                        value_map.clone(),
+                        columns.clone(),
+                    )
+                })
         })
         .collect();
 
@@ -81,6 +101,8 @@ async fn read_as_record_batch(
     payload: GetObjectOutput,
     schema: SchemaRef,
     json_format: JsonFormat,
+    value_map: Arc<ValueMap>,
+    columns: Option<Arc<Columns>>,
 ) -> Option<Vec<RecordBatch>> {
     if let None = payload.body.as_ref() {
         return None;
@@ -95,20 +117,23 @@ async fn read_as_record_batch(
         .await?;
 
     let batches = spawn_blocking(move || -> Result<_, Error> {
+        // arrow reads newline-delimited JSON only
         let mut rawjson = vec![];
-        GzDecoder::new(&*buf).read_to_end(&mut rawjson)?;
+        if json_format == JsonFormat::JsonL && value_map.is_empty() && columns.is_none() {
+            GzDecoder::new(&*buf).read_to_end(&mut rawjson)?;
+        } else {
+            write_jsonl(
+                GzDecoder::new(Cursor::new(buf)),
+                json_format,
+                &value_map,
+                columns.as_deref(),
+                &mut rawjson,
+            )?;
+        }
 
-        let mut reader = match json_format {
-            JsonFormat::Array => {
-                array_to_jsonl(rawjson.as_mut());
-                ReaderBuilder::new()
-                    .with_schema(schema.clone())
-                    .build(Cursor::new(&rawjson[1..rawjson.len() - 1]))?
-            }
-            JsonFormat::JsonL => ReaderBuilder::new()
-                .with_schema(schema.clone())
-                .build(Cursor::new(&rawjson[..]))?,
-        };
+        let mut reader = ReaderBuilder::new()
+            .with_schema(schema.clone())
+            .build(Cursor::new(&rawjson[..]))?;
 
         let mut batches = vec![];
         while let Some(rb) = reader.next()? {
@@ -120,23 +145,3 @@ async fn read_as_record_batch(
 
     Some(batches)
 }
-
-fn array_to_jsonl(data: &mut [u8]) {
-    let mut indent = 0;
-    let n = data.len();
-    for i in 0..n {
-        if data[i] == b',' && indent == 0 {
-            data[i] = b'\n';
-        } else if data[i] == b'{' {
-            indent += 1;
-        } else if data[i] == b'}' {
-            indent -= 1;
-        } else if i < n - 6 && &data[i..i + 6] == b"[\"new\"" {
-            data[i..i + 6].copy_from_slice(b"[10001");
-        } else if i < n - 9 && &data[i..i + 9] == b"[\"change\"" {
-            data[i..i + 9].copy_from_slice(b"[10000002");
-        } else if i < n - 9 && &data[i..i + 9] == b"[\"delete\"" {
-            data[i..i + 9].copy_from_slice(b"[10000004");
-        }
-    }
-}
@@ -0,0 +1,115 @@
+use crate::errors::ConnectorAgentError;
+use fehler::{throw, throws};
+use std::ops::Range;
+
+/// A query the `Dispatcher` can split in two, so that a partition holding most of the rows does
+/// not keep one thread busy while the others sit idle. The queries are split before any of them
+/// runs, about as finely as rayon splits work for the threads of the pool, and more where threads
+/// steal the halves, until the parts refuse to split.
+///
+/// A running partition then claims its part a chunk at a time with `split_front`, and a thread
+/// left without work steals the second half of what a partition has not claimed yet with `split`,
+/// which it runs as a partition of its own. Parts that do not split off chunks are run at once,
+/// and cannot be stolen from while they run.
+pub trait SplitQuery: Send {
+    /// The query the source runs for this part.
+    fn query(&self) -> String;
+
+    /// Split off the second half of this part, keeping the first, or return `None` if the part
+    /// is too small to be worth splitting.
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>>;
+
+    /// Split off the chunk at the start of this part for a partition to run next, keeping the
+    /// rest, or return `None` to run the whole part at once.
+    fn split_front(&mut self) -> Option<Box<dyn SplitQuery>> {
+        None
+    }
+}
+
+/// The share of the rest of its part, an eighth, a running partition claims at a time, so that
+/// the chunks get smaller as the part runs out, down to the smallest part worth splitting.
+pub(crate) const CLAIM_PARTS: u64 = 8;
+
+/// A plain query is never split.
+impl SplitQuery for String {
+    fn query(&self) -> String {
+        self.clone()
+    }
+
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>> {
+        None
+    }
+}
+
+/// A SQL query restricted to the rows whose integer `column` lies in `range`, split by halving
+/// the range. A running partition claims the range in chunks, each read by a query of its own.
+#[derive(Clone, Debug)]
+pub struct RangeQuery {
+    query: String,
+    column: String,
+    range: Range<i64>,
+    min_len: u64,
+}
+
+impl RangeQuery {
+    pub fn new(query: &str, column: &str, range: Range<i64>) -> Self {
+        RangeQuery {
+            query: query.to_string(),
+            column: column.to_string(),
+            range,
+            min_len: 1,
+        }
+    }
+
+    /// Do not split the range into parts of fewer than `min_len` values.
+    #[throws(ConnectorAgentError)]
+    pub fn with_min_len(mut self, min_len: u64) -> Self {
+        if min_len == 0 {
+            throw!(ConnectorAgentError::NotPositive("minimum length of a part"));
+        }
+        self.min_len = min_len;
+        self
+    }
+
+    pub fn range(&self) -> Range<i64> {
+        self.range.clone()
+    }
+}
+
+impl SplitQuery for RangeQuery {
+    fn query(&self) -> String {
+        format!(
+            "SELECT * FROM ({}) AS connector_agent_range WHERE {} >= {} AND {} < {}",
+            self.query, self.column, self.range.start, self.column, self.range.end
+        )
+    }
+
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>> {
+        let len = self.range.end as i128 - self.range.start as i128;
+        if len < 2 * self.min_len as i128 {
+            return None;
+        }
+        let mid = (self.range.start as i128 + len / 2) as i64;
+        let second = RangeQuery {
+            range: mid..self.range.end,
+            ..self.clone()
+        };
+        self.range.end = mid;
+        Some(Box::new(second))
+    }
+
+    fn split_front(&mut self) -> Option<Box<dyn SplitQuery>> {
+        let len = self.range.end as i128 - self.range.start as i128;
+        let front = (len / CLAIM_PARTS as i128).max(self.min_len as i128);
+        if len <= front {
+            return None;
+        }
+        let mid = (self.range.start as i128 + front) as i64;
+        let first = RangeQuery {
+            range: self.range.start..mid,
+            ..self.clone()
+        };
+        self.range.start = mid;
+        Some(Box::new(first))
+    }
+}
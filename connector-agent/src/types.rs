@@ -12,6 +12,7 @@ use crate::{
     typesystem::{ParameterizedFunc, ParameterizedOn, TypeAssoc, TypeSystem},
     writers::{Consume, PartitionWriter},
 };
+use chrono::{NaiveDate, NaiveDateTime};
 use fehler::throws;
 use std::marker::PhantomData;
 /// This is our intermediate type system used in this library.
@@ -21,14 +22,45 @@ use std::marker::PhantomData;
 pub enum DataType {
     F64,
     U64,
+    I64,
     Bool,
     String,
     OptU64,
+    OptI64,
+    OptF64,
+    OptBool,
+    OptString,
+    OptDate,
+    OptDateTime,
+    OptBytes,
+    OptI64List,
+    OptF64List,
+    OptBoolList,
+    OptStringList,
 }
 
 impl TypeSystem for DataType {}
 
-associate_typesystem!(DataType, DataType::F64 => f64, DataType::U64 => u64, DataType::Bool => bool, DataType::String => String, DataType::OptU64 => Option<u64>);
+associate_typesystem!(
+    DataType,
+    DataType::F64 => f64,
+    DataType::U64 => u64,
+    DataType::I64 => i64,
+    DataType::Bool => bool,
+    DataType::String => String,
+    DataType::OptU64 => Option<u64>,
+    DataType::OptI64 => Option<i64>,
+    DataType::OptF64 => Option<f64>,
+    DataType::OptBool => Option<bool>,
+    DataType::OptString => Option<String>,
+    DataType::OptDate => Option<NaiveDate>,
+    DataType::OptDateTime => Option<NaiveDateTime>,
+    DataType::OptBytes => Option<Vec<u8>>,
+    DataType::OptI64List => Option<Vec<Option<i64>>>,
+    DataType::OptF64List => Option<Vec<Option<f64>>>,
+    DataType::OptBoolList => Option<Vec<Option<bool>>>,
+    DataType::OptStringList => Option<Vec<Option<String>>>
+);
 
 pub struct Transmit<'a, S, W>(PhantomData<(&'a S, W)>);
 
@@ -1,6 +1,10 @@
-use arrow::array::{ArrayBuilder, BooleanBuilder, Float64Builder, StringBuilder, UInt64Builder};
+use arrow::array::{
+    ArrayBuilder, BinaryBuilder, BooleanBuilder, Date32Builder, Float64Builder, Int64Builder,
+    ListBuilder, StringBuilder, TimestampMicrosecondBuilder, UInt64Builder,
+};
 use arrow::datatypes::DataType as ArrowDataType;
-use arrow::datatypes::Field;
+use arrow::datatypes::{DateUnit, Field, TimeUnit};
+use chrono::{NaiveDate, NaiveDateTime};
 
 /// Associate arrow builder with native type
 pub trait ArrowAssoc {
@@ -90,3 +94,229 @@ impl ArrowAssoc for String {
         Field::new(header, ArrowDataType::Utf8, false)
     }
 }
+
+impl ArrowAssoc for i64 {
+    type Builder = Int64Builder;
+
+    fn builder(nrows: usize) -> Int64Builder {
+        Int64Builder::new(nrows)
+    }
+
+    fn append(builder: &mut Int64Builder, value: i64) {
+        builder.append_value(value).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Int64, false)
+    }
+}
+
+impl ArrowAssoc for Option<i64> {
+    type Builder = Int64Builder;
+
+    fn builder(nrows: usize) -> Int64Builder {
+        Int64Builder::new(nrows)
+    }
+
+    fn append(builder: &mut Int64Builder, value: Option<i64>) {
+        builder.append_option(value).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Int64, true)
+    }
+}
+
+impl ArrowAssoc for Option<f64> {
+    type Builder = Float64Builder;
+
+    fn builder(nrows: usize) -> Float64Builder {
+        Float64Builder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<f64>) {
+        builder.append_option(value).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Float64, true)
+    }
+}
+
+impl ArrowAssoc for Option<bool> {
+    type Builder = BooleanBuilder;
+
+    fn builder(nrows: usize) -> BooleanBuilder {
+        BooleanBuilder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<bool>) {
+        builder.append_option(value).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Boolean, true)
+    }
+}
+
+impl ArrowAssoc for Option<String> {
+    type Builder = StringBuilder;
+
+    fn builder(nrows: usize) -> StringBuilder {
+        StringBuilder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<String>) {
+        match value {
+            Some(s) => builder.append_value(s.as_str()).unwrap(),
+            None => builder.append_null().unwrap(),
+        }
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Utf8, true)
+    }
+}
+
+impl ArrowAssoc for Option<NaiveDate> {
+    type Builder = Date32Builder;
+
+    fn builder(nrows: usize) -> Date32Builder {
+        Date32Builder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<NaiveDate>) {
+        // arrow stores Date32 as the number of days since the UNIX epoch
+        let days = value.map(|d| (d - NaiveDate::from_ymd(1970, 1, 1)).num_days() as i32);
+        builder.append_option(days).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Date32(DateUnit::Day), true)
+    }
+}
+
+impl ArrowAssoc for Option<NaiveDateTime> {
+    type Builder = TimestampMicrosecondBuilder;
+
+    fn builder(nrows: usize) -> TimestampMicrosecondBuilder {
+        TimestampMicrosecondBuilder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<NaiveDateTime>) {
+        let micros =
+            value.map(|dt| dt.timestamp() * 1_000_000 + dt.timestamp_subsec_micros() as i64);
+        builder.append_option(micros).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(
+            header,
+            ArrowDataType::Timestamp(TimeUnit::Microsecond, None),
+            true,
+        )
+    }
+}
+
+impl ArrowAssoc for Option<Vec<u8>> {
+    type Builder = BinaryBuilder;
+
+    fn builder(nrows: usize) -> BinaryBuilder {
+        BinaryBuilder::new(nrows)
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<Vec<u8>>) {
+        match value {
+            Some(b) => builder.append_value(b.as_slice()).unwrap(),
+            None => builder.append_null().unwrap(),
+        }
+    }
+
+    fn field(header: &str) -> Field {
+        Field::new(header, ArrowDataType::Binary, true)
+    }
+}
+
+fn list_field(header: &str, item: ArrowDataType) -> Field {
+    let item = Field::new("item", item, true);
+    Field::new(header, ArrowDataType::List(Box::new(item)), true)
+}
+
+impl ArrowAssoc for Option<Vec<Option<i64>>> {
+    type Builder = ListBuilder<Int64Builder>;
+
+    fn builder(nrows: usize) -> ListBuilder<Int64Builder> {
+        ListBuilder::new(Int64Builder::new(nrows))
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<Vec<Option<i64>>>) {
+        for v in value.iter().flatten() {
+            builder.values().append_option(*v).unwrap();
+        }
+        builder.append(value.is_some()).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        list_field(header, ArrowDataType::Int64)
+    }
+}
+
+impl ArrowAssoc for Option<Vec<Option<f64>>> {
+    type Builder = ListBuilder<Float64Builder>;
+
+    fn builder(nrows: usize) -> ListBuilder<Float64Builder> {
+        ListBuilder::new(Float64Builder::new(nrows))
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<Vec<Option<f64>>>) {
+        for v in value.iter().flatten() {
+            builder.values().append_option(*v).unwrap();
+        }
+        builder.append(value.is_some()).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        list_field(header, ArrowDataType::Float64)
+    }
+}
+
+impl ArrowAssoc for Option<Vec<Option<bool>>> {
+    type Builder = ListBuilder<BooleanBuilder>;
+
+    fn builder(nrows: usize) -> ListBuilder<BooleanBuilder> {
+        ListBuilder::new(BooleanBuilder::new(nrows))
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<Vec<Option<bool>>>) {
+        for v in value.iter().flatten() {
+            builder.values().append_option(*v).unwrap();
+        }
+        builder.append(value.is_some()).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        list_field(header, ArrowDataType::Boolean)
+    }
+}
+
+impl ArrowAssoc for Option<Vec<Option<String>>> {
+    type Builder = ListBuilder<StringBuilder>;
+
+    fn builder(nrows: usize) -> ListBuilder<StringBuilder> {
+        ListBuilder::new(StringBuilder::new(nrows))
+    }
+
+    fn append(builder: &mut Self::Builder, value: Option<Vec<Option<String>>>) {
+        for v in value.iter().flatten() {
+            match v {
+                Some(s) => builder.values().append_value(s.as_str()).unwrap(),
+                None => builder.values().append_null().unwrap(),
+            }
+        }
+        builder.append(value.is_some()).unwrap();
+    }
+
+    fn field(header: &str) -> Field {
+        list_field(header, ArrowDataType::Utf8)
+    }
+}
@@ -14,6 +14,9 @@ use std::sync::Arc;
 
 mod arrow_assoc;
 mod funcs;
+mod stream;
+
+pub use stream::{ArrowStreamPartitionWriter, ArrowStreamWriter, RecordBatchStream};
 
 type Builder = Box<dyn Any + Send>;
 type Builders = Vec<Builder>;
@@ -121,6 +124,18 @@ impl<'a> PartitionWriter<'a> for ArrowPartitionWriter<'a> {
         self.nrows
     }
 
+    /// The builders grow on demand, so this only extends the rows the writer accepts.
+    fn grow(&mut self, nrows: usize) -> Result<()> {
+        self.nrows += nrows;
+        Ok(())
+    }
+
+    /// Nothing is appended to the builders for rows that are never written.
+    fn shrink(&mut self, nrows: usize) -> Result<()> {
+        self.nrows = nrows;
+        Ok(())
+    }
+
     fn ncols(&self) -> usize {
         self.schema.len()
     }
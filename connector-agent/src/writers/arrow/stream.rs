@@ -0,0 +1,353 @@
+use super::funcs::{FFinishBuilder, FNewBuilder, FNewField};
+use super::{arrow_assoc::ArrowAssoc, Builders};
+use crate::cancel::CancellationToken;
+use crate::data_order::DataOrder;
+use crate::errors::{ConnectorAgentError, Result};
+use crate::types::DataType;
+use crate::typesystem::{Realize, TypeAssoc, TypeSystem};
+use crate::writers::{Consume, PartitionWriter, Writer};
+use anyhow::anyhow;
+use arrow::datatypes::{Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fehler::{throw, throws};
+use itertools::Itertools;
+use std::marker::PhantomData;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::task::Poll;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A writer that does not keep the result: every partition sends a `RecordBatch` through a
+/// bounded channel each time it has written `chunk_rows` rows, so the batches can be consumed
+/// while the dispatcher is still running. Batches of different partitions are interleaved.
+///
+/// The channel holds at most `capacity` batches, after which the partitions block until the
+/// receiver catches up. Hence the receiver must be drained from another thread than the one
+/// running the dispatcher, which `Dispatcher::run_stream` takes care of.
+///
+/// Only the batches are bounded, not what the sources hold: with the dispatcher's default
+/// `RowCount::Query`, every query is run and its whole result fetched before the first batch
+/// is sent. To keep memory bounded, read through sources fetching in batches, e.g. a
+/// `PostgresSource` with a cursor, and use `RowCount::Unknown`.
+pub struct ArrowStreamWriter {
+    headers: Vec<String>,
+    chunk_rows: usize,
+    nrows: usize,
+    schema: Vec<DataType>,
+    arrow_schema: Arc<Schema>,
+    sender: Option<SyncSender<RecordBatch>>,
+    receiver: Option<Receiver<RecordBatch>>,
+}
+
+impl ArrowStreamWriter {
+    /// Fails with `ConnectorAgentError::ZeroBatchSize` if `chunk_rows` is zero.
+    #[throws(ConnectorAgentError)]
+    pub fn new(headers: Vec<String>, chunk_rows: usize, capacity: usize) -> Self {
+        if chunk_rows == 0 {
+            throw!(ConnectorAgentError::ZeroBatchSize);
+        }
+        let (sender, receiver) = sync_channel(capacity);
+        ArrowStreamWriter {
+            headers,
+            chunk_rows,
+            nrows: 0,
+            schema: vec![],
+            arrow_schema: Arc::new(Schema::empty()),
+            sender: Some(sender),
+            receiver: Some(receiver),
+        }
+    }
+
+    /// Take the receiving end of the channel the batches are sent to. Returns `None` if it was
+    /// taken already.
+    pub fn take_receiver(&mut self) -> Option<Receiver<RecordBatch>> {
+        self.receiver.take()
+    }
+}
+
+impl<'a> Writer<'a> for ArrowStreamWriter {
+    // a batch can only be sent once all the columns of its rows are written
+    const DATA_ORDERS: &'static [DataOrder] = &[DataOrder::RowMajor];
+    type TypeSystem = DataType;
+    type PartitionWriter = ArrowStreamPartitionWriter<'a>;
+
+    #[throws(ConnectorAgentError)]
+    fn allocate(&mut self, nrows: usize, schema: Vec<DataType>, _data_order: DataOrder) {
+        let fields: Vec<Field> = schema
+            .iter()
+            .zip_eq(self.headers.iter())
+            .map(|(&dt, h)| Realize::<FNewField>::realize(dt)(h.as_str()))
+            .collect();
+
+        self.nrows = nrows;
+        self.schema = schema;
+        self.arrow_schema = Arc::new(Schema::new(fields));
+    }
+
+    fn partition_writers(&'a mut self, counts: &[usize]) -> Vec<Self::PartitionWriter> {
+        assert_eq!(counts.iter().sum::<usize>(), self.nrows);
+        let sender = self
+            .sender
+            .as_ref()
+            .expect("partition writers of a finalized ArrowStreamWriter");
+
+        counts
+            .iter()
+            .map(|&c| {
+                ArrowStreamPartitionWriter::new(
+                    self.schema.clone(),
+                    Arc::clone(&self.arrow_schema),
+                    sender.clone(),
+                    self.chunk_rows,
+                    c,
+                )
+            })
+            .collect()
+    }
+
+    fn schema(&self) -> &[DataType] {
+        self.schema.as_slice()
+    }
+
+    /// Close the channel once the partitions, which hold the other senders, are done.
+    fn finalize(&mut self) -> Result<()> {
+        self.sender = None;
+        Ok(())
+    }
+}
+
+pub struct ArrowStreamPartitionWriter<'a> {
+    nrows: usize,
+    schema: Vec<DataType>,
+    arrow_schema: Arc<Schema>,
+    sender: SyncSender<RecordBatch>,
+    chunk_rows: usize,
+    builders: Builders,
+    // rows in `builders` that are not sent yet
+    buffered: usize,
+    closed: bool,
+    _writer: PhantomData<&'a ()>,
+}
+
+impl<'a> ArrowStreamPartitionWriter<'a> {
+    fn new(
+        schema: Vec<DataType>,
+        arrow_schema: Arc<Schema>,
+        sender: SyncSender<RecordBatch>,
+        chunk_rows: usize,
+        nrows: usize,
+    ) -> Self {
+        let builders = new_builders(&schema, chunk_rows.min(nrows));
+        ArrowStreamPartitionWriter {
+            nrows,
+            schema,
+            arrow_schema,
+            sender,
+            chunk_rows,
+            builders,
+            buffered: 0,
+            closed: false,
+            _writer: PhantomData,
+        }
+    }
+
+    /// Send the buffered rows as a batch, unless there are none.
+    fn flush(&mut self) -> Result<()> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let builders = std::mem::replace(
+            &mut self.builders,
+            new_builders(&self.schema, self.chunk_rows),
+        );
+        let columns = builders
+            .into_iter()
+            .zip(self.schema.iter())
+            .map(|(builder, &dt)| Realize::<FFinishBuilder>::realize(dt)(builder))
+            .collect();
+        let batch = RecordBatch::try_new(Arc::clone(&self.arrow_schema), columns)
+            .map_err(|e| anyhow!(e))?;
+        self.buffered = 0;
+
+        if self.sender.send(batch).is_err() {
+            self.closed = true;
+            return Err(ConnectorAgentError::StreamClosed);
+        }
+        Ok(())
+    }
+
+    /// Count a row as written once its last column is, sending a batch if `chunk_rows` are due.
+    fn row_written(&mut self, col: usize) -> Result<()> {
+        if col + 1 == self.schema.len() {
+            self.buffered += 1;
+            if self.buffered == self.chunk_rows {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn new_builders(schema: &[DataType], nrows: usize) -> Builders {
+    schema
+        .iter()
+        .map(|&dt| Realize::<FNewBuilder>::realize(dt)(nrows))
+        .collect()
+}
+
+impl<'a> PartitionWriter<'a> for ArrowStreamPartitionWriter<'a> {
+    type TypeSystem = DataType;
+
+    fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// Rows are sent as they are written, so this only extends the rows the writer accepts.
+    fn grow(&mut self, nrows: usize) -> Result<()> {
+        self.nrows += nrows;
+        Ok(())
+    }
+
+    /// Send the rows of the last, partially filled chunk.
+    fn finalize(&mut self) -> Result<()> {
+        if self.closed {
+            return Err(ConnectorAgentError::StreamClosed);
+        }
+        self.flush()
+    }
+
+    /// Nothing is buffered for rows that are never written.
+    fn shrink(&mut self, nrows: usize) -> Result<()> {
+        self.nrows = nrows;
+        Ok(())
+    }
+
+    fn ncols(&self) -> usize {
+        self.schema.len()
+    }
+}
+
+impl<'a, T> Consume<T> for ArrowStreamPartitionWriter<'a>
+where
+    T: TypeAssoc<<Self as PartitionWriter<'a>>::TypeSystem> + ArrowAssoc + 'static,
+{
+    /// Once the receiver is gone the values are dropped, and `finalize` reports the stream closed.
+    unsafe fn consume(&mut self, _row: usize, col: usize, value: T) {
+        if self.closed {
+            return;
+        }
+        <T as ArrowAssoc>::append(
+            self.builders[col].downcast_mut::<T::Builder>().unwrap(),
+            value,
+        );
+        // a failed send is remembered in `closed`
+        let _ = self.row_written(col);
+    }
+
+    fn consume_checked(&mut self, _row: usize, col: usize, value: T) -> Result<()> {
+        self.schema[col].check::<T>()?;
+        if self.closed {
+            return Err(ConnectorAgentError::StreamClosed);
+        }
+        <T as ArrowAssoc>::append(
+            self.builders[col].downcast_mut::<T::Builder>().unwrap(),
+            value,
+        );
+        self.row_written(col)
+    }
+}
+
+/// The batches sent by an `ArrowStreamWriter`, in the order they arrive, followed by the error
+/// of the dispatcher if it failed. Dropping the stream before it is exhausted cancels the
+/// `CancellationToken` of the dispatcher, which interrupts the queries still running, and waits
+/// for the dispatcher to exit.
+pub struct RecordBatchStream {
+    // `None` once the stream is closed
+    receiver: Option<Receiver<RecordBatch>>,
+    handle: Option<JoinHandle<Result<()>>>,
+    cancellation: CancellationToken,
+}
+
+impl RecordBatchStream {
+    pub(crate) fn new(
+        receiver: Receiver<RecordBatch>,
+        handle: JoinHandle<Result<()>>,
+        cancellation: CancellationToken,
+    ) -> Self {
+        RecordBatchStream {
+            receiver: Some(receiver),
+            handle: Some(handle),
+            cancellation,
+        }
+    }
+
+    /// Like `next`, but give up waiting for the next batch after `timeout`, e.g. to check for
+    /// interrupts in between.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Poll<Option<Result<RecordBatch>>> {
+        let receiver = match self.receiver.as_ref() {
+            Some(receiver) => receiver,
+            None => return Poll::Ready(None),
+        };
+        match receiver.recv_timeout(timeout) {
+            Ok(batch) => Poll::Ready(Some(Ok(batch))),
+            Err(RecvTimeoutError::Timeout) => Poll::Pending,
+            Err(RecvTimeoutError::Disconnected) => Poll::Ready(self.join()),
+        }
+    }
+
+    /// Stop the dispatcher if it is still running and wait for it to exit, dropping the batches
+    /// not received yet. Returns the error of the dispatcher, which is
+    /// `ConnectorAgentError::Cancelled` if it is stopped before it finishes.
+    pub fn close(mut self) -> Result<()> {
+        let handle = match self.handle.take() {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        self.stop();
+        match handle.join() {
+            // the partitions fail to send once the receiver is dropped
+            Ok(Err(ConnectorAgentError::StreamClosed)) => Err(ConnectorAgentError::Cancelled),
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Wait for the dispatcher to finish once all the senders are dropped.
+    fn join(&mut self) -> Option<Result<RecordBatch>> {
+        match self.handle.take()?.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(e)),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Cancel the dispatcher and drop the receiver, so that the partitions blocked on a full
+    /// channel fail to send instead of waiting for it forever.
+    fn stop(&mut self) {
+        self.cancellation.cancel();
+        self.receiver = None;
+    }
+}
+
+impl Iterator for RecordBatchStream {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.as_ref()?.recv() {
+            Ok(batch) => Some(Ok(batch)),
+            // all the senders are dropped: the dispatcher is done
+            Err(_) => self.join(),
+        }
+    }
+}
+
+impl Drop for RecordBatchStream {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop();
+            // the error, or panic, of a dispatcher stopped early is of no interest
+            let _ = handle.join();
+        }
+    }
+}
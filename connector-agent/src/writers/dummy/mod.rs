@@ -7,3 +7,103 @@ pub use bool_writer::{BoolPartitionWriter, BoolWriter};
 pub use f64_writer::{F64PartitionWriter, F64Writer};
 pub use string_writer::{StringPartitionWriter, StringWriter};
 pub use u64_writer::{U64PartitionWriter, U64Writer};
+
+use super::partition_rows::{concat_rows, PartitionRows};
+use super::{Consume, PartitionWriter};
+use crate::errors::Result;
+use crate::types::DataType;
+use crate::typesystem::{TypeAssoc, TypeSystem};
+use ndarray::{Array2, ArrayViewMut2, Axis};
+
+/// The `PartitionWriter` of the dummy writers, which buffer values of a single type `T`.
+pub struct ArrayPartitionWriter<'a, T> {
+    buffer: ArrayViewMut2<'a, T>,
+    rows: &'a mut PartitionRows<Array2<T>>,
+    schema: Vec<DataType>,
+}
+
+/// Split `buffer` into a partition writer for each of `counts`, keeping the rows of every
+/// partition in `parts` for `finalize`.
+fn partition_writers<'a, T>(
+    buffer: &'a mut Array2<T>,
+    parts: &'a mut Vec<PartitionRows<Array2<T>>>,
+    schema: &[DataType],
+    counts: &[usize],
+) -> Vec<ArrayPartitionWriter<'a, T>> {
+    let mut start = 0;
+    *parts = counts
+        .iter()
+        .map(|&c| {
+            start += c;
+            PartitionRows::new(start - c..start)
+        })
+        .collect();
+
+    let mut mut_view = buffer.view_mut();
+    let mut ret = vec![];
+    for (&c, rows) in counts.iter().zip(parts.iter_mut()) {
+        let (splitted, rest) = mut_view.split_at(Axis(0), c);
+        mut_view = rest;
+        ret.push(ArrayPartitionWriter {
+            buffer: splitted,
+            rows,
+            schema: schema.to_vec(),
+        });
+    }
+    ret
+}
+
+/// Gather the rows of the partition writers that grew or shrank into a single buffer.
+fn finalize<T: Default>(buffer: &mut Array2<T>, parts: &mut Vec<PartitionRows<Array2<T>>>) {
+    if !parts.iter().all(|p| p.is_allocated()) {
+        *buffer = concat_rows(buffer, parts, |chunk| chunk);
+    }
+    parts.clear();
+}
+
+impl<'a, T> PartitionWriter<'a> for ArrayPartitionWriter<'a, T>
+where
+    T: Default + Clone + Send,
+{
+    type TypeSystem = DataType;
+
+    fn nrows(&self) -> usize {
+        self.rows.nrows()
+    }
+
+    fn grow(&mut self, nrows: usize) -> Result<()> {
+        let chunk = Array2::default((nrows, self.ncols()));
+        self.rows.grow(nrows, chunk);
+        Ok(())
+    }
+
+    fn shrink(&mut self, nrows: usize) -> Result<()> {
+        self.rows.shrink(nrows);
+        Ok(())
+    }
+
+    fn ncols(&self) -> usize {
+        self.buffer.ncols()
+    }
+}
+
+impl<'a, T, V> Consume<V> for ArrayPartitionWriter<'a, T>
+where
+    T: Default + Clone + Send,
+    V: TypeAssoc<<Self as PartitionWriter<'a>>::TypeSystem> + 'static,
+{
+    unsafe fn consume(&mut self, row: usize, col: usize, value: V) {
+        // the schema holds `T` only, so checked values are `T`
+        let target = match self.rows.locate(row) {
+            Some((chunk, row)) => chunk.uget_mut((row, col)) as *mut T,
+            None => self.buffer.uget_mut((row, col)) as *mut T,
+        };
+        *(target as *mut V) = value;
+    }
+
+    fn consume_checked(&mut self, row: usize, col: usize, value: V) -> Result<()> {
+        self.schema[col].check::<V>()?;
+        unsafe { self.write(row, col, value) };
+        Ok(())
+    }
+}
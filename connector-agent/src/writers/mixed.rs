@@ -1,3 +1,4 @@
+use super::partition_rows::{concat_rows, PartitionRows};
 use super::{Consume, PartitionWriter, Writer};
 use crate::any_array::{AnyArray, AnyArrayViewMut};
 use crate::data_order::DataOrder;
@@ -10,12 +11,19 @@ use ndarray::{Array2, ArrayView1, ArrayView2, Axis, Ix2};
 use std::any::type_name;
 use std::collections::HashMap;
 
+/// The buffers a `MemoryPartitionWriter` allocates each time it grows, one for each buffer
+/// of the `MemoryWriter`.
+type Chunk = Vec<AnyArray<Ix2>>;
+
 /// This `Writer` can only write u64 into it.
 pub struct MemoryWriter {
     nrows: usize,
     schema: Vec<DataType>,
     buffers: Vec<AnyArray<Ix2>>,
+    // the type and the number of columns of each buffer
+    buffer_schema: Vec<(DataType, usize)>,
     column_buffer_index: Vec<(usize, usize)>,
+    parts: Vec<PartitionRows<Chunk>>,
 }
 
 impl MemoryWriter {
@@ -24,7 +32,9 @@ impl MemoryWriter {
             nrows: 0,
             schema: vec![],
             buffers: vec![],
+            buffer_schema: vec![],
             column_buffer_index: vec![],
+            parts: vec![],
         }
     }
 }
@@ -58,6 +68,7 @@ impl<'a> Writer<'a> for MemoryWriter {
             let count = grp.count();
             let buffer = Realize::<FArray2>::realize(dt)(nrows, count);
             self.buffers.push(buffer);
+            self.buffer_schema.push((dt, count));
         }
 
         let mut per_buffer_counter = HashMap::new();
@@ -72,6 +83,15 @@ impl<'a> Writer<'a> for MemoryWriter {
     fn partition_writers(&'a mut self, counts: &[usize]) -> Vec<Self::PartitionWriter> {
         assert_eq!(counts.iter().sum::<usize>(), self.nrows);
 
+        let mut start = 0;
+        self.parts = counts
+            .iter()
+            .map(|&c| {
+                start += c;
+                PartitionRows::new(start - c..start)
+            })
+            .collect();
+
         let nbuffers = self.buffers.len();
         let mut views: Vec<_> = self
             .buffers
@@ -79,7 +99,7 @@ impl<'a> Writer<'a> for MemoryWriter {
             .map(|buf| Some(buf.view_mut()))
             .collect();
         let mut ret = vec![];
-        for &c in counts {
+        for (&c, rows) in counts.iter().zip(self.parts.iter_mut()) {
             let mut sub_buffers = vec![];
 
             for bid in 0..nbuffers {
@@ -89,9 +109,10 @@ impl<'a> Writer<'a> for MemoryWriter {
                 sub_buffers.push(splitted);
             }
             ret.push(MemoryPartitionWriter::new(
-                c,
                 sub_buffers,
+                rows,
                 self.schema.clone(),
+                self.buffer_schema.clone(),
                 self.column_buffer_index.clone(),
             ));
         }
@@ -101,6 +122,23 @@ impl<'a> Writer<'a> for MemoryWriter {
     fn schema(&self) -> &[DataType] {
         self.schema.as_slice()
     }
+
+    /// Gather the rows of the partition writers that grew or shrank into a single buffer
+    /// for each type.
+    fn finalize(&mut self) -> Result<()> {
+        if !self.parts.iter().all(|p| p.is_allocated()) {
+            for (bid, &(dt, _)) in self.buffer_schema.iter().enumerate() {
+                self.buffers[bid] = Realize::<FConcatRows>::realize(dt)(
+                    &mut self.buffers[bid],
+                    &mut self.parts,
+                    bid,
+                );
+            }
+            self.nrows = self.parts.iter().map(|p| p.nrows()).sum();
+        }
+        self.parts.clear();
+        Ok(())
+    }
 }
 
 impl MemoryWriter {
@@ -128,23 +166,26 @@ impl MemoryWriter {
 }
 /// The `PartitionedWriter` of `MemoryWriter`.
 pub struct MemoryPartitionWriter<'a> {
-    nrows: usize,
     buffers: Vec<AnyArrayViewMut<'a, Ix2>>,
+    rows: &'a mut PartitionRows<Chunk>,
     schema: Vec<DataType>,
+    buffer_schema: Vec<(DataType, usize)>,
     column_buffer_index: Vec<(usize, usize)>,
 }
 
 impl<'a> MemoryPartitionWriter<'a> {
     fn new(
-        nrows: usize,
         buffers: Vec<AnyArrayViewMut<'a, Ix2>>,
+        rows: &'a mut PartitionRows<Chunk>,
         schema: Vec<DataType>,
+        buffer_schema: Vec<(DataType, usize)>,
         column_buffer_index: Vec<(usize, usize)>,
     ) -> Self {
         Self {
-            nrows,
             buffers,
+            rows,
             schema,
+            buffer_schema,
             column_buffer_index,
         }
     }
@@ -154,12 +195,27 @@ impl<'a> PartitionWriter<'a> for MemoryPartitionWriter<'a> {
     type TypeSystem = DataType;
 
     fn nrows(&self) -> usize {
-        self.nrows
+        self.rows.nrows()
     }
 
     fn ncols(&self) -> usize {
         self.schema.len()
     }
+
+    fn grow(&mut self, nrows: usize) -> Result<()> {
+        let chunk = self
+            .buffer_schema
+            .iter()
+            .map(|&(dt, ncols)| Realize::<FArray2>::realize(dt)(nrows, ncols))
+            .collect();
+        self.rows.grow(nrows, chunk);
+        Ok(())
+    }
+
+    fn shrink(&mut self, nrows: usize) -> Result<()> {
+        self.rows.shrink(nrows);
+        Ok(())
+    }
 }
 
 impl<'a, T> Consume<T> for MemoryPartitionWriter<'a>
@@ -168,24 +224,37 @@ where
 {
     unsafe fn consume(&mut self, row: usize, col: usize, value: T) {
         let &(bid, col) = &self.column_buffer_index[col];
-        let mut_view = self.buffers[bid].udowncast::<T>();
-        *mut_view.get_mut((row, col)).unwrap() = value;
+        match self.rows.locate(row) {
+            Some((chunk, row)) => {
+                let arr = chunk[bid].downcast_mut::<T>().unwrap();
+                *arr.uget_mut((row, col)) = value;
+            }
+            None => {
+                let mut_view = self.buffers[bid].udowncast::<T>();
+                *mut_view.get_mut((row, col)).unwrap() = value;
+            }
+        }
     }
 
     fn consume_checked(&mut self, row: usize, col: usize, value: T) -> Result<()> {
         self.schema[col].check::<T>()?;
         let &(bid, col) = &self.column_buffer_index[col];
 
-        let mut_view =
-            self.buffers[bid]
-                .downcast::<T>()
-                .ok_or(ConnectorAgentError::UnexpectedType(
-                    self.schema[col],
-                    type_name::<T>(),
-                ))?;
-        *mut_view
-            .get_mut((row, col))
-            .ok_or(ConnectorAgentError::OutOfBound)? = value;
+        let unexpected_type =
+            ConnectorAgentError::UnexpectedType(self.schema[col], type_name::<T>());
+        match self.rows.locate(row) {
+            Some((chunk, row)) => {
+                let arr = chunk[bid].downcast_mut::<T>().ok_or(unexpected_type)?;
+                *arr.get_mut((row, col))
+                    .ok_or(ConnectorAgentError::OutOfBound)? = value;
+            }
+            None => {
+                let mut_view = self.buffers[bid].downcast::<T>().ok_or(unexpected_type)?;
+                *mut_view
+                    .get_mut((row, col))
+                    .ok_or(ConnectorAgentError::OutOfBound)? = value;
+            }
+        }
         Ok(())
     }
 }
@@ -210,3 +279,36 @@ where
         create_any_array::<T>
     }
 }
+
+struct FConcatRows;
+
+impl ParameterizedFunc for FConcatRows {
+    type Function = fn(
+        buffer: &mut AnyArray<Ix2>,
+        parts: &mut [PartitionRows<Chunk>],
+        bid: usize,
+    ) -> AnyArray<Ix2>;
+}
+
+impl<T> ParameterizedOn<T> for FConcatRows
+where
+    T: Default + Send + 'static,
+{
+    fn parameterize() -> Self::Function {
+        fn concat<T>(
+            buffer: &mut AnyArray<Ix2>,
+            parts: &mut [PartitionRows<Chunk>],
+            bid: usize,
+        ) -> AnyArray<Ix2>
+        where
+            T: Default + Send + 'static,
+        {
+            let buffer = buffer.downcast_mut::<T>().unwrap();
+            concat_rows(buffer, parts, |chunk| {
+                chunk[bid].downcast_mut::<T>().unwrap()
+            })
+            .into()
+        }
+        concat::<T>
+    }
+}
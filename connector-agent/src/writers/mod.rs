@@ -1,9 +1,10 @@
 pub mod arrow;
 pub mod dummy;
 pub mod mixed;
+mod partition_rows;
 
 use crate::data_order::DataOrder;
-use crate::errors::Result;
+use crate::errors::{ConnectorAgentError, Result};
 use crate::typesystem::{TypeAssoc, TypeSystem};
 
 /// A `Writer` is associated with a `TypeSystem` and a `PartitionWriter`.
@@ -27,6 +28,12 @@ pub trait Writer<'a>: Sized {
     fn partition_writers(&'a mut self, counts: &[usize]) -> Vec<Self::PartitionWriter>;
     /// Return the schema of the writer.
     fn schema(&self) -> &[Self::TypeSystem];
+
+    /// Called after all the partition writers are done, e.g. to gather the rows that partition
+    /// writers wrote beyond their allocation.
+    fn finalize(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// `PartitionWriter` writes values to its own region. `PartitionWriter` is parameterized
@@ -55,6 +62,24 @@ pub trait PartitionWriter<'a>: Send {
     }
     /// Number of rows this `PartitionWriter` controls.
     fn nrows(&self) -> usize;
+    /// Extend this `PartitionWriter` by `nrows` rows after the ones it already controls.
+    /// Writers that cannot grow beyond their allocation return `ConnectorAgentError::CannotGrow`.
+    fn grow(&mut self, _nrows: usize) -> Result<()> {
+        Err(ConnectorAgentError::CannotGrow)
+    }
+    /// Called once all the rows of the partition are written.
+    fn finalize(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Drop the rows after the first `nrows`, which were allocated but never written.
+    /// Writers that cannot give back rows return `ConnectorAgentError::CannotShrink`.
+    fn shrink(&mut self, nrows: usize) -> Result<()> {
+        if nrows == self.nrows() {
+            Ok(())
+        } else {
+            Err(ConnectorAgentError::CannotShrink)
+        }
+    }
     /// Number of rows this `PartitionWriter` controls.
     fn ncols(&self) -> usize;
 }
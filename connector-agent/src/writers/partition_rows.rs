@@ -0,0 +1,113 @@
+use ndarray::Array2;
+use std::ops::Range;
+
+/// The rows of a `PartitionWriter` that can grow: the region allocated to it in the buffer of
+/// the parent `Writer`, followed by a chunk `C` for every time it grew.
+#[derive(Clone)]
+pub(crate) struct PartitionRows<C> {
+    base: Range<usize>,
+    allocated: usize,
+    // each chunk with the row of the partition it starts at
+    chunks: Vec<(usize, C)>,
+    nrows: usize,
+}
+
+impl<C> PartitionRows<C> {
+    pub fn new(base: Range<usize>) -> Self {
+        PartitionRows {
+            nrows: base.len(),
+            allocated: base.len(),
+            base,
+            chunks: vec![],
+        }
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// Append `chunk`, which holds `nrows` more rows.
+    pub fn grow(&mut self, nrows: usize, chunk: C) {
+        self.chunks.push((self.nrows, chunk));
+        self.nrows += nrows;
+    }
+
+    /// Keep only the first `nrows` rows.
+    pub fn shrink(&mut self, nrows: usize) {
+        assert!(nrows <= self.nrows);
+        self.nrows = nrows;
+        self.base.end = self.base.end.min(self.base.start + nrows);
+        self.chunks.retain(|&(start, _)| start < nrows);
+    }
+
+    /// Find the chunk holding `row` and the position of `row` in that chunk.
+    /// `None` means `row` lies in the region allocated in the parent buffer.
+    pub fn locate(&mut self, row: usize) -> Option<(&mut C, usize)> {
+        if row < self.base.len() {
+            return None;
+        }
+        let i = match self.chunks.binary_search_by_key(&row, |&(start, _)| start) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let (start, chunk) = &mut self.chunks[i];
+        Some((chunk, row - *start))
+    }
+
+    /// Whether the partition holds exactly the rows allocated to it, i.e. it never grew or shrank.
+    pub fn is_allocated(&self) -> bool {
+        self.chunks.is_empty() && self.nrows == self.allocated
+    }
+}
+
+/// Move the rows of all `parts` in order into a new array, taking them out of `buffer`, the
+/// parent buffer the parts were allocated in, and out of their chunks, where `array` picks the
+/// array to read from a chunk.
+pub(crate) fn concat_rows<T, C, F>(
+    buffer: &mut Array2<T>,
+    parts: &mut [PartitionRows<C>],
+    mut array: F,
+) -> Array2<T>
+where
+    T: Default,
+    F: FnMut(&mut C) -> &mut Array2<T>,
+{
+    let nrows = parts.iter().map(|p| p.nrows).sum();
+    let mut ret = Array2::<T>::default((nrows, buffer.ncols()));
+
+    let mut dst = 0;
+    for part in parts {
+        for src in part.base.clone() {
+            move_row(buffer, src, &mut ret, dst);
+            dst += 1;
+        }
+        // a chunk ends where the next one starts: after shrinking and growing again, the rows
+        // an earlier chunk holds beyond that were dropped
+        let ends: Vec<usize> = part
+            .chunks
+            .iter()
+            .skip(1)
+            .map(|&(start, _)| start)
+            .chain(std::iter::once(part.nrows))
+            .collect();
+        for ((start, chunk), end) in part.chunks.iter_mut().zip(ends) {
+            let chunk = array(chunk);
+            let n = chunk.nrows().min(end - *start);
+            for src in 0..n {
+                move_row(chunk, src, &mut ret, dst);
+                dst += 1;
+            }
+        }
+    }
+    ret
+}
+
+fn move_row<T: Default>(src: &mut Array2<T>, src_row: usize, dst: &mut Array2<T>, dst_row: usize) {
+    for (d, s) in dst
+        .row_mut(dst_row)
+        .iter_mut()
+        .zip(src.row_mut(src_row).iter_mut())
+    {
+        *d = std::mem::take(s);
+    }
+}
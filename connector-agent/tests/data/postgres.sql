@@ -0,0 +1,46 @@
+DROP TABLE IF EXISTS test_types;
+
+CREATE TABLE test_types (
+    test_int2 SMALLINT,
+    test_int4 INTEGER,
+    test_int8 BIGINT,
+    test_float4 REAL,
+    test_float8 DOUBLE PRECISION,
+    test_numeric NUMERIC(10, 3),
+    test_text TEXT,
+    test_varchar VARCHAR(10),
+    test_bool BOOLEAN,
+    test_date DATE,
+    test_timestamp TIMESTAMP,
+    test_bytea BYTEA
+);
+
+INSERT INTO test_types VALUES
+    (1, -2, 3000000000, 1.5, -2.25, 12345.678, 'a', 'b', TRUE, '2021-01-01', '2021-01-01 12:34:56.789', '\x0102'),
+    (NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL);
+
+DROP TABLE IF EXISTS test_custom_types;
+DROP DOMAIN IF EXISTS test_posint;
+DROP TYPE IF EXISTS test_mood;
+
+CREATE DOMAIN test_posint AS INTEGER CHECK (VALUE > 0);
+CREATE TYPE test_mood AS ENUM ('sad', 'happy');
+
+CREATE TABLE test_custom_types (
+    test_domain test_posint,
+    test_enum test_mood,
+    test_int4_array INTEGER[],
+    test_float8_array DOUBLE PRECISION[],
+    test_bool_array BOOLEAN[],
+    test_text_array TEXT[],
+    test_domain_array test_posint[]
+);
+
+INSERT INTO test_custom_types VALUES
+    (1, 'happy', '{1,NULL,3}', '{0.5}', '{TRUE,FALSE}', '{"a",NULL}', '{2}'),
+    (NULL, NULL, NULL, NULL, NULL, NULL, NULL),
+    (2, 'sad', '{}', '{}', '{}', '{}', '{}');
+
+DROP TABLE IF EXISTS test_ctid;
+
+CREATE TABLE test_ctid AS SELECT i AS test_int, i::text AS test_text FROM generate_series(1, 10000) i;
@@ -2,11 +2,14 @@ use arrow::array::{BooleanArray, Float64Array, StringArray, UInt64Array};
 use arrow::record_batch::RecordBatch;
 use connector_agent::{
     data_sources::{dummy::OptU64SourceBuilder, mixed::MixedSourceBuilder},
-    writers::arrow::ArrowWriter,
-    DataType, Dispatcher,
+    writers::arrow::{ArrowStreamWriter, ArrowWriter},
+    CancellationToken, ConnectorAgentError, DataType, Dispatcher, RowCount,
 };
 use itertools::Itertools;
 use rand::Rng;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 fn test_arrow() {
@@ -173,3 +176,114 @@ fn test_option_arrow() {
         }
     }
 }
+
+#[test]
+fn test_arrow_unknown_row_count() {
+    let schema = vec![DataType::U64, DataType::String];
+    let headers = vec!["c0".to_string(), "c1".to_string()];
+    let queries = vec!["4,2".to_string(), "7,2".to_string()];
+
+    // the partition writers start empty and grow while writing
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        ArrowWriter::new(),
+        schema,
+        queries,
+    )
+    .with_row_count(RowCount::Unknown);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    let records: Vec<RecordBatch> = dw.finish(headers);
+    assert_eq!(2, records.len());
+    assert_eq!(4, records[0].num_rows());
+    assert_eq!(7, records[1].num_rows());
+    assert!(records[1]
+        .column(0)
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .unwrap()
+        .eq(&UInt64Array::from(vec![0, 1, 2, 3, 4, 5, 6])));
+}
+
+#[test]
+fn test_arrow_stream() {
+    let schema = vec![DataType::U64, DataType::String];
+    let headers = vec!["c0".to_string(), "c1".to_string()];
+    let queries = vec!["4,2".to_string(), "7,2".to_string()];
+
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        ArrowStreamWriter::new(headers, 3, 1).expect("create writer"),
+        schema,
+        queries,
+    );
+    let records: Vec<RecordBatch> = dispatcher
+        .run_stream()
+        .collect::<Result<_, _>>()
+        .expect("run dispatcher");
+
+    // 4 rows in chunks of 3 + 1 and 7 rows in chunks of 3 + 3 + 1
+    assert_eq!(5, records.len());
+    assert!(records.iter().all(|rb| rb.num_rows() <= 3));
+
+    let mut values: Vec<u64> = records
+        .iter()
+        .flat_map(|rb| {
+            let col = rb.column(0).as_any().downcast_ref::<UInt64Array>().unwrap();
+            (0..col.len()).map(|i| col.value(i)).collect::<Vec<_>>()
+        })
+        .collect();
+    values.sort();
+    assert_eq!(vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 5, 6], values);
+
+    match ArrowStreamWriter::new(vec!["c0".to_string()], 0, 1) {
+        Err(ConnectorAgentError::ZeroBatchSize) => {}
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("batches of no rows are sent"),
+    }
+}
+
+#[test]
+fn test_arrow_stream_dropped() {
+    let schema = vec![DataType::U64, DataType::String];
+    let headers = vec!["c0".to_string(), "c1".to_string()];
+    let queries = vec!["1000,2".to_string(), "1000,2".to_string()];
+    let run = || {
+        let token = CancellationToken::new();
+        let dispatcher = Dispatcher::new(
+            MixedSourceBuilder::new(),
+            ArrowStreamWriter::new(headers.clone(), 10, 1).expect("create writer"),
+            schema.clone(),
+            queries.clone(),
+        )
+        .with_cancellation(token.clone());
+        let mut stream = dispatcher.run_stream();
+        let first = stream.next().unwrap().expect("first batch");
+        assert_eq!(10, first.num_rows());
+        (stream, token)
+    };
+
+    // the partitions fail on their next send instead of blocking forever, and closing the
+    // stream waits for them
+    let (stream, token) = run();
+    let (done, closed) = channel();
+    thread::spawn(move || done.send(stream.close()).unwrap());
+    match closed.recv_timeout(Duration::from_secs(10)) {
+        Ok(Err(ConnectorAgentError::Cancelled)) => {}
+        Ok(result) => panic!("the run is not cancelled: {:?}", result),
+        Err(_) => panic!("the dispatcher does not exit"),
+    }
+    assert!(token.is_cancelled());
+
+    // dropping the stream waits for the dispatcher as well
+    let (stream, token) = run();
+    let (done, dropped) = channel();
+    thread::spawn(move || {
+        drop(stream);
+        done.send(()).unwrap()
+    });
+    dropped
+        .recv_timeout(Duration::from_secs(10))
+        .expect("the dispatcher exits");
+    assert!(token.is_cancelled());
+}
@@ -0,0 +1,36 @@
+use connector_agent::CancellationToken;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn cancel_callbacks() {
+    let token = CancellationToken::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let count = |calls: &Arc<AtomicUsize>| {
+        let calls = Arc::clone(calls);
+        move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+        }
+    };
+
+    // the callbacks of dropped guards never run
+    for _ in 0..1000 {
+        let _guard = token.on_cancel(count(&calls));
+    }
+    let _kept = token.on_cancel(count(&calls));
+
+    // callbacks run outside of the lock, so they can use the token themselves
+    let inner = token.clone();
+    let nested = Arc::clone(&calls);
+    let _reentrant = token.on_cancel(move || {
+        let _guard = inner.on_cancel(count(&nested));
+    });
+
+    token.cancel();
+    token.cancel();
+    assert_eq!(2, calls.load(Ordering::SeqCst));
+
+    // once cancelled, callbacks run right away
+    let _late = token.on_cancel(count(&calls));
+    assert_eq!(3, calls.load(Ordering::SeqCst));
+}
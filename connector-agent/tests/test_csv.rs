@@ -1,10 +1,18 @@
 use connector_agent::data_sources::{
-    csv::{CSVSource, CSVSourceBuilder},
-    DataSource, Produce,
+    compression::Compression,
+    csv::{CSVSource, CSVSourceBuilder, OnError},
+    files, DataSource, Produce, SourceBuilder,
+};
+use connector_agent::writers::{dummy::U64Writer, mixed::MemoryWriter};
+use connector_agent::{
+    ConnectorAgentError, DataType, Dispatcher, PartitionProgress, Phase, RowCount, SplitQuery,
+    TextOptions,
 };
-use connector_agent::writers::dummy::U64Writer;
-use connector_agent::{DataType, Dispatcher};
 use ndarray::array;
+use rayon::ThreadPoolBuilder;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 #[test]
 #[should_panic]
@@ -106,3 +114,664 @@ fn test_csv() {
         dw.buffer()
     );
 }
+
+#[test]
+fn test_csv_batches() {
+    for &row_count in &[RowCount::Query, RowCount::Exact, RowCount::Unknown] {
+        let schema = vec![DataType::U64; 5];
+        let files = vec![
+            "./tests/data/uint_0.csv".to_string(),
+            "./tests/data/uint_1.csv".to_string(),
+        ];
+        let builder = CSVSourceBuilder::new().with_batch_size(2).unwrap();
+        let dispatcher =
+            Dispatcher::new(builder, U64Writer::new(), schema, files).with_row_count(row_count);
+
+        let dw = dispatcher.run_checked().expect("run dispatcher");
+        assert_eq!(
+            array![
+                [0, 1, 2, 3, 4],
+                [5, 6, 7, 8, 9],
+                [10, 11, 12, 13, 14],
+                [15, 16, 17, 18, 19],
+                [20, 21, 22, 23, 24],
+                [25, 26, 27, 28, 29],
+                [30, 31, 32, 33, 34],
+                [35, 36, 37, 38, 39],
+                [40, 41, 42, 43, 44],
+                [45, 46, 47, 48, 49],
+                [50, 51, 52, 53, 54],
+            ],
+            dw.buffer(),
+            "{:?}",
+            row_count
+        );
+    }
+}
+
+#[test]
+fn eager_reads() {
+    // the records are read a batch at a time unless the source is eager
+    let builder = CSVSourceBuilder::new().with_batch_size(2).unwrap();
+    let mut source = builder.with_eager(false).build();
+    source
+        .run_query("./tests/data/uint_1.csv")
+        .expect("run query");
+    assert_eq!(2, source.nrows);
+
+    let mut source = CSVSourceBuilder::new()
+        .with_batch_size(2)
+        .unwrap()
+        .with_eager(true)
+        .build();
+    source
+        .run_query("./tests/data/uint_1.csv")
+        .expect("run query");
+    assert_eq!(7, source.nrows);
+    let v: u64 = source.produce().expect("produce first value");
+    assert_eq!(20, v);
+}
+
+#[test]
+fn zero_sizes() {
+    let builder = CSVSourceBuilder::new();
+    assert!(matches!(
+        CSVSourceBuilder::new().with_batch_size(0),
+        Err(ConnectorAgentError::ZeroBatchSize)
+    ));
+    assert!(matches!(
+        CSVSource::new().with_batch_size(0),
+        Err(ConnectorAgentError::ZeroBatchSize)
+    ));
+    assert!(matches!(
+        CSVSourceBuilder::new().with_sample_rows(0),
+        Err(ConnectorAgentError::NotPositive(_))
+    ));
+    assert!(matches!(
+        builder.byte_ranges("./tests/data/uint_0.csv", 0),
+        Err(ConnectorAgentError::NotPositive(_))
+    ));
+    assert!(matches!(
+        builder.grouped_queries("./tests/data/parts", 0),
+        Err(ConnectorAgentError::NotPositive(_))
+    ));
+    assert!(matches!(
+        builder
+            .split_query("./tests/data/uint_0.csv")
+            .unwrap()
+            .with_min_len(0),
+        Err(ConnectorAgentError::NotPositive(_))
+    ));
+}
+
+#[test]
+fn count_rows() {
+    let mut source = CSVSource::new();
+    assert_eq!(
+        7,
+        source
+            .count_rows("./tests/data/uint_1.csv", RowCount::Exact)
+            .expect("count rows")
+    );
+    // small files are counted exactly
+    assert_eq!(
+        7,
+        source
+            .count_rows("./tests/data/uint_1.csv", RowCount::Estimate)
+            .expect("estimate rows")
+    );
+
+    let mut source = dialect_builder().build();
+    assert_eq!(
+        3,
+        source
+            .count_rows("./tests/data/dialect.csv", RowCount::Exact)
+            .expect("count rows")
+    );
+}
+
+fn dialect_builder() -> CSVSourceBuilder {
+    CSVSourceBuilder::new()
+        .with_skip_rows(1)
+        .with_comment(b'#')
+        .with_headers(true)
+        .with_delimiter(b';')
+        .with_quote(Some(b'\''))
+        .with_trim(true)
+        .with_flexible(true)
+}
+
+#[test]
+fn load_dialect() {
+    let builder = dialect_builder();
+    assert_eq!(
+        vec!["city", "state", "population"],
+        builder
+            .headers("./tests/data/dialect.csv")
+            .expect("read headers")
+    );
+
+    let mut source = dialect_builder().build();
+    source
+        .run_query("./tests/data/dialect.csv")
+        .expect("run query");
+    assert_eq!(3, source.nrows);
+    assert_eq!(3, source.ncols);
+    assert_eq!(
+        Some(
+            &[
+                "city".to_string(),
+                "state".to_string(),
+                "population".to_string()
+            ][..]
+        ),
+        source.headers()
+    );
+
+    let mut results: Vec<(String, String, Option<u64>)> = vec![];
+    for _i in 0..source.nrows {
+        results.push((
+            source.produce().expect("parse city"),
+            source.produce().expect("parse state"),
+            source.produce().expect("parse population"),
+        ));
+    }
+    assert_eq!(
+        vec![
+            ("Kenai; AK".to_string(), "AK".to_string(), Some(7610)),
+            ("It's".to_string(), "AL".to_string(), Some(18980)),
+            ("El Mirage".to_string(), "AZ".to_string(), None),
+        ],
+        results
+    );
+}
+
+#[test]
+fn unequal_lengths() {
+    let mut source = dialect_builder().with_flexible(false).build();
+    assert!(source.run_query("./tests/data/dialect.csv").is_err());
+}
+
+#[test]
+fn test_csv_dialect() {
+    let schema = vec![DataType::String, DataType::String, DataType::OptU64];
+    let files = vec!["./tests/data/dialect.csv".to_string(); 2];
+    let dispatcher = Dispatcher::new(dialect_builder(), MemoryWriter::new(), schema, files);
+
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(
+        vec![Some(7610), Some(18980), None, Some(7610), Some(18980), None],
+        dw.column_view::<Option<u64>>(2).unwrap().to_vec()
+    );
+}
+
+fn run_invalid(
+    builder: CSVSourceBuilder,
+    schema: Vec<DataType>,
+) -> Result<MemoryWriter, ConnectorAgentError> {
+    let files = vec!["./tests/data/invalid.csv".to_string()];
+    Dispatcher::new(builder, MemoryWriter::new(), schema, files).run_checked()
+}
+
+#[test]
+fn parse_error() {
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::OptBool];
+    match run_invalid(CSVSourceBuilder::new(), schema) {
+        Err(ConnectorAgentError::CSVParseError {
+            path,
+            line,
+            col,
+            text,
+            ty,
+        }) => {
+            assert_eq!("./tests/data/invalid.csv", path);
+            assert_eq!((2, 0), (line, col));
+            assert_eq!(("x", "u64"), (text.as_str(), ty));
+        }
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("invalid values are parsed"),
+    }
+}
+
+#[test]
+fn parse_error_null() {
+    let schema = vec![DataType::OptU64, DataType::OptF64, DataType::OptBool];
+    let builder = CSVSourceBuilder::new().with_on_error(OnError::Null);
+    let dw = run_invalid(builder, schema).expect("run dispatcher");
+    assert_eq!(
+        vec![Some(1), None, Some(3)],
+        dw.column_view::<Option<u64>>(0).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![Some(true), Some(false), None],
+        dw.column_view::<Option<bool>>(2).unwrap().to_vec()
+    );
+
+    // values of columns that are not nullable still fail
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::OptBool];
+    let builder = CSVSourceBuilder::new().with_on_error(OnError::Null);
+    assert!(run_invalid(builder, schema).is_err());
+}
+
+#[test]
+fn parse_error_default() {
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::Bool];
+    let builder = CSVSourceBuilder::new().with_on_error(OnError::Default);
+    let dw = run_invalid(builder, schema).expect("run dispatcher");
+    assert_eq!(vec![1, 0, 3], dw.column_view::<u64>(0).unwrap().to_vec());
+    assert_eq!(
+        vec![Some(2.5), Some(3.5), None],
+        dw.column_view::<Option<f64>>(1).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![true, false, false],
+        dw.column_view::<bool>(2).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn parse_error_skip() {
+    let rejects = std::env::temp_dir().join(format!("rejects_{}.csv", std::process::id()));
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::OptBool];
+    let builder = CSVSourceBuilder::new()
+        .with_on_error(OnError::Skip)
+        .with_schema(schema.clone())
+        .with_reject_file(rejects.to_str().unwrap());
+    let dw = run_invalid(builder, schema).expect("run dispatcher");
+    assert_eq!(vec![1], dw.column_view::<u64>(0).unwrap().to_vec());
+
+    let rejected = fs::read_to_string(&rejects).expect("read reject file");
+    fs::remove_file(&rejects).unwrap();
+    assert_eq!(
+        "./tests/data/invalid.csv,2,x,3.5,false\n./tests/data/invalid.csv,3,3,,maybe\n",
+        rejected
+    );
+}
+
+#[test]
+fn parse_error_skip_without_schema() {
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::OptBool];
+    let builder = CSVSourceBuilder::new().with_on_error(OnError::Skip);
+    assert!(run_invalid(builder, schema).is_err());
+}
+
+#[test]
+fn null_and_bool_spellings() {
+    let schema = vec![DataType::OptU64, DataType::Bool, DataType::OptF64];
+    let text = TextOptions::postgres()
+        .with_null_values(&["NULL", "\\N"])
+        .with_column(0, TextOptions::default().with_null_values(&["NA"]));
+    let builder = CSVSourceBuilder::new().with_headers(true).with_text(text);
+    let files = vec!["./tests/data/spellings.csv".to_string()];
+    let dw = Dispatcher::new(builder, MemoryWriter::new(), schema, files)
+        .run_checked()
+        .expect("run dispatcher");
+
+    assert_eq!(
+        vec![Some(1), Some(2), None],
+        dw.column_view::<Option<u64>>(0).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![true, false, true],
+        dw.column_view::<bool>(1).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![None, None, Some(3.5)],
+        dw.column_view::<Option<f64>>(2).unwrap().to_vec()
+    );
+
+    // `t` is no boolean by default
+    let schema = vec![DataType::OptU64, DataType::Bool, DataType::OptF64];
+    let builder = CSVSourceBuilder::new()
+        .with_headers(true)
+        .with_null_values(&["NULL", "\\N", "NA"]);
+    let files = vec!["./tests/data/spellings.csv".to_string()];
+    assert!(Dispatcher::new(builder, MemoryWriter::new(), schema, files)
+        .run_checked()
+        .is_err());
+}
+
+#[test]
+fn compressed() {
+    for &path in &[
+        "./tests/data/uint_1.csv.gz",
+        "./tests/data/uint_1.csv.zst",
+        "./tests/data/uint_1.csv.bz2",
+        // detected by its first bytes
+        "./tests/data/uint_1.bin",
+    ] {
+        let mut source = CSVSourceBuilder::new().build();
+        assert_eq!(
+            7,
+            source
+                .count_rows(path, RowCount::Estimate)
+                .expect("count rows"),
+            "{}",
+            path
+        );
+
+        let schema = vec![DataType::U64; 5];
+        let files = vec!["./tests/data/uint_0.csv".to_string(), path.to_string()];
+        let dispatcher = Dispatcher::new(CSVSourceBuilder::new(), U64Writer::new(), schema, files);
+        let dw = dispatcher.run_checked().expect("run dispatcher");
+        assert_eq!((11, 5), dw.buffer().dim(), "{}", path);
+        assert_eq!(54, dw.buffer()[[10, 4]], "{}", path);
+    }
+}
+
+#[test]
+fn compressed_members() {
+    // gzip files of several members are read to the end
+    let mut source = CSVSourceBuilder::new()
+        .with_compression(Compression::Gzip)
+        .build();
+    source
+        .run_query("./tests/data/uint_1_twice.gz")
+        .expect("run query");
+    assert_eq!(14, source.nrows);
+
+    // compressed files are read as they are when told so
+    let mut source = CSVSourceBuilder::new()
+        .with_compression(Compression::None)
+        .build();
+    source
+        .run_query("./tests/data/uint_1.csv.gz")
+        .expect("run query");
+    assert!(Produce::<u64>::produce(&mut source).is_err());
+}
+
+#[test]
+fn detect_compression() {
+    assert_eq!(Compression::Gzip, Compression::detect("a.csv.GZ", b""));
+    assert_eq!(
+        Compression::Zstd,
+        Compression::detect("a", &[0x28, 0xb5, 0x2f, 0xfd, 0])
+    );
+    assert_eq!(Compression::None, Compression::detect("a.csv", b"BZh,a,b"));
+}
+
+#[test]
+fn byte_ranges() {
+    let mut builder = CSVSourceBuilder::new().with_headers(true);
+    let queries = builder
+        .byte_ranges("./tests/data/quoted.csv", 4)
+        .expect("split file");
+    assert_eq!(4, queries.len());
+
+    let mut source = builder.build();
+    let counts: Vec<usize> = queries
+        .iter()
+        .map(|query| source.count_rows(query, RowCount::Exact).unwrap())
+        .collect();
+    assert_eq!(20, counts.iter().sum::<usize>());
+    assert!(counts.iter().all(|&n| n > 0), "{:?}", counts);
+
+    // the records spanning several lines are not cut at the range boundaries
+    let schema = vec![DataType::U64, DataType::String];
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(
+        (0..20).collect::<Vec<u64>>(),
+        dw.column_view::<u64>(0).unwrap().to_vec()
+    );
+    let texts = dw.column_view::<String>(1).unwrap();
+    assert_eq!("row 2", texts[2]);
+    assert_eq!("row 3\nspans, \"two\" lines", texts[3]);
+
+    // a file of few records is split in fewer ranges, and a compressed one not at all
+    let builder = CSVSourceBuilder::new();
+    assert_eq!(
+        4,
+        builder
+            .byte_ranges("./tests/data/uint_0.csv", 10)
+            .unwrap()
+            .len()
+    );
+    assert_eq!(
+        vec!["./tests/data/uint_1.csv.gz"],
+        builder
+            .byte_ranges("./tests/data/uint_1.csv.gz", 10)
+            .unwrap()
+    );
+}
+
+#[test]
+fn split_byte_ranges() {
+    let builder = CSVSourceBuilder::new().with_headers(true);
+    let query = builder
+        .split_query("./tests/data/quoted.csv")
+        .expect("split query")
+        .with_min_len(1)
+        .unwrap();
+    assert_eq!("./tests/data/quoted.csv", query.query());
+
+    // a range is split at the start of a record, however small it gets
+    let mut first = query.clone();
+    let second = first.split().expect("split range");
+    let mid = first.range().end;
+    assert!(mid > 0 && mid < 418);
+    assert_eq!(
+        format!("./tests/data/quoted.csv#bytes={}-418", mid),
+        second.query()
+    );
+
+    // a running partition claims the range a chunk at a time, ending at the start of a record
+    let mut rest = query.clone();
+    let chunk = rest.split_front().expect("claim chunk");
+    assert_eq!(
+        format!("./tests/data/quoted.csv#bytes=0-{}", rest.range().start),
+        chunk.query()
+    );
+    assert!(rest.range().start > 0 && rest.range().end == 418);
+
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let partitions = Arc::new(AtomicUsize::new(0));
+    let allocated = Arc::clone(&partitions);
+    let schema = vec![DataType::U64, DataType::String];
+    let dispatcher = Dispatcher::new_split(builder, MemoryWriter::new(), schema, vec![query])
+        .with_thread_pool(Arc::new(pool))
+        .with_progress(move |p: &PartitionProgress| {
+            if p.phase == Phase::Allocate {
+                allocated.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    // the parts read the records of the whole file, in order unless the rest of a part is
+    // stolen by a thread that is done
+    assert!(partitions.load(Ordering::SeqCst) > 1);
+    let ids = dw.column_view::<u64>(0).unwrap().to_vec();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!((0..20).collect::<Vec<u64>>(), sorted);
+    let row = ids.iter().position(|&id| id == 3).unwrap();
+    assert_eq!(
+        "row 3\nspans, \"two\" lines",
+        dw.column_view::<String>(1).unwrap()[row]
+    );
+
+    // compressed files are read whole
+    let mut query = CSVSourceBuilder::new()
+        .split_query("./tests/data/uint_1.csv.gz")
+        .unwrap()
+        .with_min_len(1)
+        .unwrap();
+    assert!(query.split().is_none());
+}
+
+#[test]
+fn glob_and_directory() {
+    let builder = CSVSourceBuilder::new().with_headers(true);
+    assert_eq!(
+        vec![
+            "tests/data/parts/2021-01.csv",
+            "tests/data/parts/2021-02.csv"
+        ],
+        builder.queries("./tests/data/parts/2021-*.csv").unwrap()
+    );
+    // the files in a directory are spelled the same way
+    assert_eq!(
+        vec![
+            "tests/data/parts/2020-12.csv",
+            "tests/data/parts/2021-01.csv",
+            "tests/data/parts/2021-02.csv"
+        ],
+        builder.queries("./tests/data/parts").unwrap()
+    );
+    assert!(builder.queries("./tests/data/parts/1999-*.csv").is_err());
+
+    // the small files are read together
+    let queries = builder
+        .grouped_queries("./tests/data/parts", 1 << 20)
+        .unwrap();
+    assert_eq!(1, queries.len());
+    assert_eq!(3, files::split(&queries[0]).count());
+}
+
+#[test]
+fn filename_column() {
+    let mut builder = CSVSourceBuilder::new()
+        .with_headers(true)
+        .with_filename_column("file");
+    assert_eq!(
+        vec!["id", "name", "file"],
+        builder.headers("./tests/data/parts/2021-01.csv").unwrap()
+    );
+
+    // a query for the two files of 2021 and one for 2020, with two rows to a batch
+    let mut queries = builder.grouped_queries("./tests/data/parts", 30).unwrap();
+    assert_eq!(2, queries.len());
+    queries.reverse();
+
+    let mut source = builder.build();
+    source.run_query(&queries[0]).unwrap();
+    assert_eq!(3, source.ncols);
+    assert_eq!(3, source.count_rows(&queries[0], RowCount::Exact).unwrap());
+
+    let schema = vec![DataType::U64, DataType::String, DataType::String];
+    let builder = builder.with_batch_size(2).unwrap();
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(
+        vec![1, 2, 3, 4, 5, 6],
+        dw.column_view::<u64>(0).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![
+            "tests/data/parts/2021-01.csv",
+            "tests/data/parts/2021-01.csv",
+            "tests/data/parts/2021-02.csv",
+            "tests/data/parts/2020-12.csv",
+            "tests/data/parts/2020-12.csv",
+            "tests/data/parts/2020-12.csv",
+        ],
+        dw.column_view::<String>(2).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn infer_schema() {
+    let mut builder = CSVSourceBuilder::new().with_headers(true);
+    let queries = builder.queries("./tests/data/infer").unwrap();
+
+    // each file on its own
+    let mut source = builder.build();
+    source.run_query(&queries[0]).unwrap();
+    assert_eq!(
+        vec![
+            DataType::I64,
+            DataType::I64,
+            DataType::Bool,
+            DataType::OptDate,
+            DataType::OptDateTime,
+            DataType::String,
+            DataType::OptString,
+        ],
+        source.infer_schema().unwrap()
+    );
+
+    // widened across the partitions
+    let (names, schema) = builder.describe(&queries).unwrap();
+    assert_eq!(
+        vec!["id", "price", "flag", "day", "at", "name", "note"],
+        names
+    );
+    assert_eq!(
+        vec![
+            DataType::I64,
+            DataType::F64,
+            DataType::OptBool,
+            DataType::OptDate,
+            DataType::OptDateTime,
+            DataType::String,
+            DataType::OptString,
+        ],
+        schema
+    );
+
+    // the same when the files are read by a single query
+    let query = builder.grouped_queries("./tests/data/infer", 1000).unwrap();
+    assert_eq!(schema, builder.describe(&query).unwrap().1);
+
+    // only the first row of every file, missing the float of the second file
+    let builder = builder.with_sample_rows(1).unwrap();
+    let (_, schema) = builder.describe(&queries).unwrap();
+    assert_eq!(DataType::I64, schema[1]);
+    assert_eq!(DataType::OptBool, schema[2]);
+
+    // the inferred schema reads every file
+    let builder = CSVSourceBuilder::new().with_headers(true);
+    let (_, schema) = builder.describe(&queries).unwrap();
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        vec![2.0, 3.0, 4.0, 5.5],
+        dw.column_view::<f64>(1).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![Some(true), Some(false), None, Some(true)],
+        dw.column_view::<Option<bool>>(2).unwrap().to_vec()
+    );
+    // the bare date of a column of timestamps is its midnight
+    let day = |d| chrono::NaiveDate::from_ymd(2021, 1, d);
+    assert_eq!(
+        vec![
+            Some(day(1).and_hms(10, 0, 0)),
+            Some(day(2).and_hms(10, 0, 0)),
+            Some(day(3).and_hms(0, 0, 0)),
+            Some(day(4).and_hms(10, 0, 0)),
+        ],
+        dw.column_view::<Option<chrono::NaiveDateTime>>(4)
+            .unwrap()
+            .to_vec()
+    );
+    assert_eq!(
+        vec!["a", "b", "c", "7"],
+        dw.column_view::<String>(5).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn infer_schema_spellings() {
+    let text = TextOptions::postgres()
+        .with_null_values(&["NULL", "\\N"])
+        .with_column(0, TextOptions::default().with_null_values(&["NA"]));
+    let builder = CSVSourceBuilder::new()
+        .with_headers(true)
+        .with_text(text)
+        .with_filename_column("file");
+    let (names, schema) = builder
+        .describe(&["./tests/data/spellings.csv".to_string()])
+        .unwrap();
+    assert_eq!(vec!["id", "flag", "score", "file"], names);
+    assert_eq!(
+        vec![
+            DataType::OptI64,
+            DataType::Bool,
+            DataType::OptF64,
+            DataType::String
+        ],
+        schema
+    );
+}
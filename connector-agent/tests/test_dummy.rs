@@ -3,9 +3,9 @@ use connector_agent::data_sources::dummy::{
 };
 use connector_agent::writers::{
     dummy::{BoolWriter, F64Writer, StringWriter, U64Writer},
-    Writer,
+    PartitionWriter, Writer,
 };
-use connector_agent::{DataOrder, DataType, Dispatcher};
+use connector_agent::{DataOrder, DataType, Dispatcher, RowCount};
 use ndarray::array;
 
 #[test]
@@ -72,6 +72,42 @@ fn write_array() {
     );
 }
 
+#[test]
+fn write_array_unknown_row_count() {
+    let schema = vec![DataType::U64; 2];
+    let queries = vec!["3".to_string(), "0".to_string(), "2".to_string()];
+
+    let dispatcher = Dispatcher::new(U64SourceBuilder {}, U64Writer::new(), schema, queries)
+        .with_row_count(RowCount::Unknown);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(array![[0, 1], [2, 3], [4, 5], [0, 1], [2, 3]], dw.buffer());
+}
+
+#[test]
+fn shrink_then_grow() {
+    let mut dw = U64Writer::new();
+    dw.allocate(2, vec![DataType::U64], DataOrder::RowMajor)
+        .unwrap();
+    {
+        let mut pw = dw.partition_writers(&[2]).pop().unwrap();
+        pw.grow(4).unwrap();
+        for row in 0..6 {
+            pw.write_checked(row, 0, row as u64).unwrap();
+        }
+        // the rows dropped from the first chunk are not read back once the writer grows again
+        pw.shrink(4).unwrap();
+        pw.grow(2).unwrap();
+        for row in 4..6 {
+            pw.write_checked(row, 0, 10 + row as u64).unwrap();
+        }
+        pw.finalize().unwrap();
+    }
+    dw.finalize().unwrap();
+
+    assert_eq!(array![[0], [1], [2], [3], [14], [15]], dw.buffer());
+}
+
 #[test]
 fn write_string_array() {
     let schema = vec![DataType::String; 5];
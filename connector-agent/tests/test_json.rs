@@ -0,0 +1,464 @@
+use connector_agent::data_sources::{
+    json::{write_jsonl, ArrayReader, Columns, FieldPath, JsonFormat, JsonSourceBuilder, ValueMap},
+    DataSource, Produce, SourceBuilder,
+};
+use connector_agent::writers::mixed::MemoryWriter;
+use connector_agent::{ConnectorAgentError, DataType, Dispatcher, RowCount};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Cursor;
+
+fn orders_builder() -> JsonSourceBuilder {
+    JsonSourceBuilder::new(&["id", "price", "name", "active", "day", "at", "tags"]).unwrap()
+}
+
+fn orders_schema() -> Vec<DataType> {
+    vec![
+        DataType::U64,
+        DataType::OptF64,
+        DataType::String,
+        DataType::Bool,
+        DataType::OptDate,
+        DataType::OptDateTime,
+        DataType::OptStringList,
+    ]
+}
+
+#[test]
+fn read_jsonl() {
+    for &(row_count, eager) in &[
+        (RowCount::Query, false),
+        (RowCount::Exact, false),
+        (RowCount::Unknown, false),
+        (RowCount::Query, true),
+    ] {
+        let files = vec![
+            "./tests/data/orders.jsonl".to_string(),
+            "./tests/data/orders.jsonl.gz".to_string(),
+        ];
+        let builder = orders_builder()
+            .with_batch_size(2)
+            .unwrap()
+            .with_eager(eager);
+        let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), orders_schema(), files)
+            .with_row_count(row_count);
+        let dw = dispatcher.run_checked().expect("run dispatcher");
+
+        assert_eq!(
+            vec![1, 2, 3, 1, 2, 3],
+            dw.column_view::<u64>(0).unwrap().to_vec()
+        );
+        assert_eq!(
+            vec![Some(2.5), Some(3.0), None],
+            dw.column_view::<Option<f64>>(1).unwrap().to_vec()[..3]
+        );
+        assert_eq!(
+            vec!["a", "b", "c"],
+            dw.column_view::<String>(2).unwrap().to_vec()[..3]
+        );
+        assert_eq!(
+            vec![true, false, true],
+            dw.column_view::<bool>(3).unwrap().to_vec()[..3]
+        );
+        assert_eq!(
+            vec![Some(chrono::NaiveDate::from_ymd(2021, 1, 1)), None, None],
+            dw.column_view::<Option<chrono::NaiveDate>>(4)
+                .unwrap()
+                .to_vec()[..3]
+        );
+        assert_eq!(
+            vec![
+                None,
+                Some(chrono::NaiveDate::from_ymd(2021, 1, 2).and_hms(10, 0, 0)),
+                None
+            ],
+            dw.column_view::<Option<chrono::NaiveDateTime>>(5)
+                .unwrap()
+                .to_vec()[..3]
+        );
+        assert_eq!(
+            vec![Some(vec![Some("x".to_string()), None]), None, Some(vec![])],
+            dw.column_view::<Option<Vec<Option<String>>>>(6)
+                .unwrap()
+                .to_vec()[..3]
+        );
+    }
+}
+
+#[test]
+fn read_from_reader() {
+    let mut builder = JsonSourceBuilder::new(&["a", "b"])
+        .unwrap()
+        .with_reader(|query: &str| {
+            let body = match query {
+                "first" => "{\"a\": 1, \"b\": {\"c\": [1, 2]}}\n{\"b\": \"x\"}\n",
+                _ => "{\"a\": -1}",
+            };
+            Ok(Cursor::new(body.as_bytes().to_vec()))
+        });
+    let mut source = builder.build();
+    assert_eq!(2, source.count_rows("first", RowCount::Estimate).unwrap());
+
+    source.run_query("first").unwrap();
+    assert_eq!(2, source.nrows);
+    assert_eq!(
+        Some(1),
+        Produce::<Option<i64>>::produce(&mut source).unwrap()
+    );
+    // other values are read as their JSON text
+    assert_eq!(
+        Some("{\"c\":[1,2]}".to_string()),
+        Produce::<Option<String>>::produce(&mut source).unwrap()
+    );
+    assert_eq!(None, Produce::<Option<i64>>::produce(&mut source).unwrap());
+    assert_eq!(
+        Some("x".to_string()),
+        Produce::<Option<String>>::produce(&mut source).unwrap()
+    );
+
+    source.run_query("second").unwrap();
+    assert_eq!(-1, Produce::<i64>::produce(&mut source).unwrap());
+    match Produce::<String>::produce(&mut source) {
+        Err(ConnectorAgentError::JsonValueError {
+            path,
+            line,
+            field,
+            value,
+            ty,
+        }) => {
+            assert_eq!(("second", 1), (path.as_str(), line));
+            assert_eq!(
+                ("b", "null", "String"),
+                (field.as_str(), value.as_str(), ty)
+            );
+        }
+        _ => panic!("a missing field is read in a column that is not nullable"),
+    }
+}
+
+#[test]
+fn json_errors() {
+    let mut builder = JsonSourceBuilder::new(&["a"])
+        .unwrap()
+        .with_reader(|query: &str| {
+            let body = match query {
+                "syntax" => "{\"a\": 1}\n\n{\"a\": 2\n",
+                _ => "{\"a\": 1.5}\n",
+            };
+            Ok(Cursor::new(body.as_bytes().to_vec()))
+        });
+
+    let mut source = builder.build();
+    match source.run_query("syntax") {
+        Err(ConnectorAgentError::JsonError { path, line, .. }) => {
+            assert_eq!(("syntax", 3), (path.as_str(), line))
+        }
+        _ => panic!("invalid JSON is read"),
+    }
+
+    source.run_query("float").unwrap();
+    match Produce::<u64>::produce(&mut source) {
+        Err(ConnectorAgentError::JsonValueError { value, ty, .. }) => {
+            assert_eq!(("1.5", "u64"), (value.as_str(), ty))
+        }
+        _ => panic!("a float is read as an integer"),
+    }
+}
+
+/// The codes of the actions of an order book, replaced in the asks and bids only.
+fn book_value_map() -> ValueMap {
+    let actions = ValueMap::new()
+        .with_value("new", json!(10001))
+        .with_value("change", json!(10000002))
+        .with_value("delete", json!(10000004));
+    ValueMap::new()
+        .with_field("asks", actions.clone())
+        .with_field("bids", actions)
+}
+
+#[test]
+fn read_array() {
+    let mut builder = JsonSourceBuilder::new(&["change_id", "type", "instrument_name", "asks"])
+        .unwrap()
+        .with_format(JsonFormat::Array)
+        .with_value_map(book_value_map());
+    let mut source = builder.build();
+    assert_eq!(
+        2,
+        source
+            .count_rows("./tests/data/book.json.gz", RowCount::Exact)
+            .unwrap()
+    );
+
+    source.run_query("./tests/data/book.json.gz").unwrap();
+    assert_eq!(2, source.nrows);
+    assert_eq!(1, Produce::<u64>::produce(&mut source).unwrap());
+    // the same string is kept outside of the mapped fields
+    assert_eq!("change", Produce::<String>::produce(&mut source).unwrap());
+    assert_eq!(
+        "BTC-PERPETUAL, [ok]",
+        Produce::<String>::produce(&mut source).unwrap()
+    );
+    assert_eq!(
+        Some("[[10001,19000.5,10],[10000004,19001.0,0]]".to_string()),
+        Produce::<Option<String>>::produce(&mut source).unwrap()
+    );
+    assert_eq!(2, Produce::<u64>::produce(&mut source).unwrap());
+    assert_eq!("snapshot", Produce::<String>::produce(&mut source).unwrap());
+    assert_eq!(
+        "say \"}]\"",
+        Produce::<String>::produce(&mut source).unwrap()
+    );
+}
+
+#[test]
+fn array_reader() {
+    let input = fs::read("./tests/data/book.json").unwrap();
+    let mut reader = ArrayReader::new(&input[..]);
+    let mut lines = vec![];
+    let mut ids = vec![];
+    while reader.read_item().unwrap() {
+        let item: Value = serde_json::from_slice(reader.item()).unwrap();
+        lines.push(reader.line());
+        ids.push(item["change_id"].as_u64().unwrap());
+    }
+    assert_eq!((vec![2, 9], vec![1, 2]), (lines, ids));
+    assert!(!reader.read_item().unwrap());
+
+    let mut reader = ArrayReader::new(&b" [ ] "[..]);
+    assert!(!reader.read_item().unwrap());
+    let mut reader = ArrayReader::new(&b"[1, \"2\", [3], {\"4\": [5]}]"[..]);
+    let mut items = vec![];
+    while reader.read_item().unwrap() {
+        items.push(serde_json::from_slice::<Value>(reader.item()).unwrap());
+    }
+    assert_eq!(
+        vec![json!(1), json!("2"), json!([3]), json!({"4": [5]})],
+        items
+    );
+
+    for invalid in &[&b"{\"a\": 1}"[..], b"[1, 2", b"[1, , 2]"] {
+        let mut reader = ArrayReader::new(*invalid);
+        assert!(
+            (0..3).any(|_| reader.read_item().is_err()),
+            "{}",
+            String::from_utf8_lossy(invalid)
+        );
+    }
+}
+
+#[test]
+fn rewrite_as_jsonl() {
+    let input = fs::File::open("./tests/data/book.json").unwrap();
+    let mut output = vec![];
+    write_jsonl(
+        input,
+        JsonFormat::Array,
+        &book_value_map(),
+        None,
+        &mut output,
+    )
+    .unwrap();
+    let objects: Vec<Value> = output
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(2, objects.len());
+    assert_eq!(json!([[10000002, 18999.0, 5]]), objects[0]["bids"]);
+    assert_eq!(json!("change"), objects[0]["type"]);
+
+    // the mapping deserializes from JSON
+    let value_map: ValueMap = serde_json::from_str(
+        r#"{"fields": {"asks": {"values": {"new": 10001}}, "bids": {"values": {}}}}"#,
+    )
+    .unwrap();
+    assert!(!value_map.is_empty());
+    assert_eq!(json!(10001), value_map.fields["asks"].values["new"]);
+}
+
+#[test]
+fn read_paths() {
+    let mut builder = JsonSourceBuilder::new(&[
+        "meta.instrument",
+        "asks[0].price",
+        "asks[1].price",
+        "meta[\"a.b\"]",
+        "meta",
+    ])
+    .unwrap()
+    .with_reader(|_: &str| {
+        let body = "{\"meta\": {\"instrument\": \"BTC\", \"a.b\": 1}, \"asks\": [{\"price\": 1.5}, {\"price\": 2.5}]}\n{\"meta\": null, \"asks\": [{}]}\n";
+        Ok(Cursor::new(body.as_bytes().to_vec()))
+    });
+    let mut source = builder.build();
+    source.run_query("book").unwrap();
+    assert_eq!(2, source.nrows);
+    assert_eq!(
+        Some("BTC".to_string()),
+        Produce::<Option<String>>::produce(&mut source).unwrap()
+    );
+    assert_eq!(1.5, Produce::<f64>::produce(&mut source).unwrap());
+    assert_eq!(
+        Some(2.5),
+        Produce::<Option<f64>>::produce(&mut source).unwrap()
+    );
+    assert_eq!(
+        Some(1),
+        Produce::<Option<i64>>::produce(&mut source).unwrap()
+    );
+    // the values read by other columns are kept whole
+    let meta = Produce::<String>::produce(&mut source).unwrap();
+    assert_eq!(
+        json!({"instrument": "BTC", "a.b": 1}),
+        serde_json::from_str::<Value>(&meta).unwrap()
+    );
+
+    assert_eq!(
+        None,
+        Produce::<Option<String>>::produce(&mut source).unwrap()
+    );
+    match Produce::<f64>::produce(&mut source) {
+        Err(ConnectorAgentError::JsonValueError { field, value, .. }) => {
+            assert_eq!(("asks[0].price", "null"), (field.as_str(), value.as_str()))
+        }
+        _ => panic!("a missing value is read in a column that is not nullable"),
+    }
+    for _ in 0..3 {
+        assert_eq!(
+            None,
+            Produce::<Option<String>>::produce(&mut source).unwrap()
+        );
+    }
+}
+
+#[test]
+fn explode_arrays() {
+    for &row_count in &[RowCount::Query, RowCount::Exact, RowCount::Unknown] {
+        let builder = JsonSourceBuilder::new(&[
+            "change_id",
+            "asks[*][0]",
+            "asks[*][1]",
+            "asks[*][2]",
+            "instrument_name",
+        ])
+        .unwrap()
+        .with_format(JsonFormat::Array)
+        .with_value_map(book_value_map())
+        .with_batch_size(1)
+        .unwrap();
+        let schema = vec![
+            DataType::U64,
+            DataType::U64,
+            DataType::F64,
+            DataType::F64,
+            DataType::String,
+        ];
+        let files = vec![
+            "./tests/data/book.json".to_string(),
+            "./tests/data/book.json.gz".to_string(),
+        ];
+        let dispatcher =
+            Dispatcher::new(builder, MemoryWriter::new(), schema, files).with_row_count(row_count);
+        let dw = dispatcher.run_checked().expect("run dispatcher");
+
+        // the second object has no asks, and makes no rows
+        assert_eq!(vec![1, 1, 1, 1], dw.column_view::<u64>(0).unwrap().to_vec());
+        assert_eq!(
+            vec![10001, 10000004, 10001, 10000004],
+            dw.column_view::<u64>(1).unwrap().to_vec()
+        );
+        assert_eq!(
+            vec![19000.5, 19001.0, 19000.5, 19001.0],
+            dw.column_view::<f64>(2).unwrap().to_vec()
+        );
+        assert_eq!(
+            vec![10.0, 0.0],
+            dw.column_view::<f64>(3).unwrap().to_vec()[..2]
+        );
+        assert_eq!(
+            vec!["BTC-PERPETUAL, [ok]"; 4],
+            dw.column_view::<String>(4).unwrap().to_vec()
+        );
+    }
+
+    let mut builder = JsonSourceBuilder::new(&["id", "asks[*]"])
+        .unwrap()
+        .with_reader(|_: &str| Ok(Cursor::new(b"{\"id\": 1, \"asks\": 2}".to_vec())));
+    let mut source = builder.build();
+    for result in vec![
+        source.count_rows("asks", RowCount::Exact),
+        source.run_query("asks").map(|_| 0),
+    ] {
+        match result {
+            Err(ConnectorAgentError::JsonValueError {
+                field, value, ty, ..
+            }) => assert_eq!(("asks", "2", "array"), (field.as_str(), value.as_str(), ty)),
+            _ => panic!("a number is exploded"),
+        }
+    }
+}
+
+#[test]
+fn field_paths() {
+    let path = FieldPath::parse("a[\"b.c\"][2].d[*]").unwrap();
+    assert_eq!("a[\"b.c\"][2].d[*]", path.as_str());
+    assert!(!path.is_field());
+    assert!(FieldPath::parse("a b").unwrap().is_field());
+
+    for invalid in &[
+        "",
+        "a.",
+        ".a",
+        "a..b",
+        "a[",
+        "a[x]",
+        "a[-1]",
+        "[0]",
+        "a[*][*]",
+        "a[\"b\"",
+        "a[\"b\"]c",
+        "a]b[",
+    ] {
+        match FieldPath::parse(invalid) {
+            Err(ConnectorAgentError::FieldPathError { path, .. }) => assert_eq!(*invalid, path),
+            _ => panic!("{:?} is parsed", invalid),
+        }
+    }
+
+    let columns = Columns::new(&["id", "asks[*][0]", "asks[*][1]"]).unwrap();
+    assert_eq!((3, Some("asks")), (columns.len(), columns.exploded()));
+    assert!(!columns.is_flat());
+    assert!(Columns::new(&["id", "asks"]).unwrap().is_flat());
+    assert!(matches!(
+        Columns::new(&["asks[*]", "bids[*]"]),
+        Err(ConnectorAgentError::FieldPathError { .. })
+    ));
+}
+
+#[test]
+fn rewrite_flattened() {
+    let input = fs::File::open("./tests/data/book.json").unwrap();
+    let columns = Columns::new(&["change_id", "asks[*][1]", "bids[0][0]"]).unwrap();
+    let mut output = vec![];
+    write_jsonl(
+        input,
+        JsonFormat::Array,
+        &book_value_map(),
+        Some(&columns),
+        &mut output,
+    )
+    .unwrap();
+    let rows: Vec<Value> = output
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(
+        vec![
+            json!({"change_id": 1, "asks[*][1]": 19000.5, "bids[0][0]": 10000002}),
+            json!({"change_id": 1, "asks[*][1]": 19001.0, "bids[0][0]": 10000002}),
+        ],
+        rows
+    );
+}
@@ -1,9 +1,14 @@
 use connector_agent::{
-    data_sources::mixed::MixedSourceBuilder, writers::mixed::MemoryWriter, DataOrder, DataType,
-    Dispatcher, PartitionWriter, SourceBuilder, Writer,
+    data_sources::mixed::MixedSourceBuilder, writers::mixed::MemoryWriter, CancellationToken,
+    ConnectorAgentError, DataOrder, DataType, Dispatcher, PartitionProgress, PartitionWriter,
+    Phase, RowCount, SourceBuilder, SplitQuery, Writer,
 };
 use ndarray::array;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::ThreadPoolBuilder;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 #[test]
 #[should_panic]
@@ -220,3 +225,296 @@ fn test_mixed() {
         }
     }
 }
+
+#[test]
+fn test_mixed_exact_row_count() {
+    let schema = vec![DataType::U64, DataType::String];
+    let queries = vec!["4,2".to_string(), "3,2".to_string()];
+
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        schema,
+        queries,
+    )
+    .with_row_count(RowCount::Exact);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        dw.column_view::<u64>(0).unwrap(),
+        array![0, 1, 2, 3, 0, 1, 2]
+    );
+}
+
+#[test]
+fn test_mixed_unknown_row_count() {
+    // the partition writers start empty, grow while writing and are concatenated at the end
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String, DataType::U64],
+        vec!["4,3".to_string(), "0,3".to_string(), "3,3".to_string()],
+    )
+    .with_row_count(RowCount::Unknown);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        dw.column_view::<u64>(0).unwrap(),
+        array![0, 1, 2, 3, 0, 1, 2]
+    );
+    assert_eq!(
+        dw.column_view::<String>(1).unwrap(),
+        array!["0", "1", "2", "3", "0", "1", "2"].map(|s| s.to_string())
+    );
+    assert_eq!(
+        dw.column_view::<u64>(2).unwrap(),
+        array![0, 1, 2, 3, 0, 1, 2]
+    );
+}
+
+#[test]
+fn test_mixed_progress() {
+    let reports: Arc<Mutex<Vec<PartitionProgress>>> = Arc::new(Mutex::new(vec![]));
+    let collected = Arc::clone(&reports);
+
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String],
+        vec!["5000,2".to_string(), "3,2".to_string()],
+    )
+    .with_progress(move |p: &PartitionProgress| collected.lock().unwrap().push(*p));
+    dispatcher.run_checked().expect("run dispatcher");
+
+    let reports = reports.lock().unwrap();
+    for (partition, total, rows) in vec![(0, 5000, vec![4096, 5000]), (1, 3, vec![3])] {
+        let reports: Vec<_> = reports
+            .iter()
+            .filter(|p| p.partition == partition)
+            .collect();
+        assert!(reports.iter().all(|p| p.query == partition));
+        assert_eq!(Phase::Query, reports[0].phase);
+        assert_eq!(Phase::Allocate, reports[1].phase);
+        assert_eq!(Some(total), reports[1].total);
+        assert!(reports[2..].iter().all(|p| p.phase == Phase::Transfer));
+        assert_eq!(
+            rows,
+            reports[2..].iter().map(|p| p.rows).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn test_mixed_cancelled() {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+
+    // cancel the run from the progress callback once the first rows are written
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String],
+        vec!["10000,2".to_string(), "10000,2".to_string()],
+    )
+    .with_cancellation(token)
+    .with_progress(move |p: &PartitionProgress| {
+        if p.phase == Phase::Transfer {
+            cancel.cancel();
+        }
+    });
+
+    match dispatcher.run_checked() {
+        Err(ConnectorAgentError::Cancelled) => {}
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the run is not cancelled"),
+    }
+}
+
+#[test]
+fn test_mixed_thread_pool() {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(2)
+        .thread_name(|i| format!("dispatcher-{}", i))
+        .build()
+        .unwrap();
+    let threads: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let collected = Arc::clone(&threads);
+
+    let queries: Vec<String> = (0..8).map(|_| "3,2".to_string()).collect();
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String],
+        queries,
+    )
+    .with_thread_pool(Arc::new(pool))
+    .with_max_concurrency(1)
+    .expect("limit concurrency")
+    .with_progress(move |_: &PartitionProgress| {
+        let name = thread::current().name().unwrap_or_default().to_string();
+        collected.lock().unwrap().push(name);
+    });
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(24, dw.column_view::<u64>(0).unwrap().len());
+    // the allocation is reported from the calling thread, everything else from the pool
+    let threads = threads.lock().unwrap();
+    assert_eq!(
+        16,
+        threads
+            .iter()
+            .filter(|name| name.starts_with("dispatcher-"))
+            .count()
+    );
+}
+
+#[test]
+fn test_mixed_zero_concurrency() {
+    let dispatcher = Dispatcher::new(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64],
+        vec!["3,1".to_string()],
+    );
+    match dispatcher.with_max_concurrency(0) {
+        Err(ConnectorAgentError::NotPositive(_)) => {}
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("no partition can ever run"),
+    }
+}
+
+/// Rows of a `MixedSource`, split in halves down to 100 rows.
+struct Rows(usize);
+
+impl SplitQuery for Rows {
+    fn query(&self) -> String {
+        format!("{},2", self.0)
+    }
+
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>> {
+        if self.0 < 200 {
+            return None;
+        }
+        let second = self.0 / 2;
+        self.0 -= second;
+        Some(Box::new(Rows(second)))
+    }
+}
+
+#[test]
+fn test_mixed_split() {
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let reports: Arc<Mutex<Vec<PartitionProgress>>> = Arc::new(Mutex::new(vec![]));
+    let collected = Arc::clone(&reports);
+
+    let dispatcher = Dispatcher::new_split(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String],
+        vec![Rows(10), Rows(1000)],
+    )
+    .with_row_count(RowCount::Exact)
+    .with_thread_pool(Arc::new(pool))
+    .with_progress(move |p: &PartitionProgress| collected.lock().unwrap().push(*p));
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    // every part counts its rows from 0, and the parts are written in the order of the queries
+    let col = dw.column_view::<u64>(0).unwrap();
+    assert_eq!(1010, col.len());
+    let mut starts = 0;
+    for (i, &v) in col.iter().enumerate() {
+        if v == 0 {
+            starts += 1;
+        } else {
+            assert_eq!(col[i - 1] + 1, v);
+        }
+    }
+    assert_eq!(9, col[9]);
+    assert_eq!(0, col[10]);
+
+    // a partition keeps its index from counting to transfer, its query is run once, and the
+    // parts of a query come after the parts of the queries before it
+    let reports = reports.lock().unwrap();
+    let mut queries = vec![];
+    for partition in 0..starts {
+        let reports: Vec<_> = reports
+            .iter()
+            .filter(|p| p.partition == partition)
+            .collect();
+        let phases: Vec<_> = reports.iter().map(|p| p.phase).take(3).collect();
+        assert_eq!(vec![Phase::Count, Phase::Allocate, Phase::Query], phases);
+        assert!(reports[3..].iter().all(|p| p.phase == Phase::Transfer));
+        assert_eq!(
+            reports[1].total,
+            reports.last().map(|p| Some(p.rows)).unwrap()
+        );
+        assert!(reports.iter().all(|p| p.query == reports[0].query));
+        queries.push(reports[0].query);
+    }
+    assert!(reports.iter().all(|p| p.partition < starts));
+    assert_eq!(0, queries[0]);
+    assert!(queries[1..].iter().all(|&q| q == 1));
+    assert!(starts > 2, "the large query is split");
+}
+
+/// Rows of a `MixedSource`, claimed 100 rows at a time and split in halves down to 100 rows.
+struct Chunks(usize);
+
+impl SplitQuery for Chunks {
+    fn query(&self) -> String {
+        format!("{},2", self.0)
+    }
+
+    fn split(&mut self) -> Option<Box<dyn SplitQuery>> {
+        if self.0 < 200 {
+            return None;
+        }
+        let second = self.0 / 2;
+        self.0 -= second;
+        Some(Box::new(Chunks(second)))
+    }
+
+    fn split_front(&mut self) -> Option<Box<dyn SplitQuery>> {
+        if self.0 <= 100 {
+            return None;
+        }
+        self.0 -= 100;
+        Some(Box::new(Chunks(100)))
+    }
+}
+
+#[test]
+fn test_mixed_steal() {
+    let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let reports: Arc<Mutex<Vec<PartitionProgress>>> = Arc::new(Mutex::new(vec![]));
+    let collected = Arc::clone(&reports);
+
+    // the small query is done long before the chunks of the large one
+    let dispatcher = Dispatcher::new_split(
+        MixedSourceBuilder::new(),
+        MemoryWriter::new(),
+        vec![DataType::U64, DataType::String],
+        vec![Chunks(100), Chunks(20000)],
+    )
+    .with_row_count(RowCount::Unknown)
+    .with_thread_pool(Arc::new(pool))
+    .with_progress(move |p: &PartitionProgress| {
+        if p.query == 1 && p.phase == Phase::Query {
+            thread::sleep(Duration::from_millis(1));
+        }
+        collected.lock().unwrap().push(*p);
+    });
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(20100, dw.column_view::<u64>(0).unwrap().len());
+
+    // the partitions stolen into come after the planned ones, which report their allocation
+    let reports = reports.lock().unwrap();
+    let planned = reports
+        .iter()
+        .filter(|p| p.phase == Phase::Allocate)
+        .count();
+    let stolen: Vec<_> = reports.iter().filter(|p| p.partition >= planned).collect();
+    assert!(!stolen.is_empty(), "the rest of the large query is stolen");
+    assert!(stolen.iter().all(|p| p.query == 1));
+    assert!(stolen.iter().all(|p| p.phase != Phase::Allocate));
+}
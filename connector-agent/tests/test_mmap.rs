@@ -0,0 +1,207 @@
+use connector_agent::data_sources::{
+    csv::{CSVOptions, CSVSourceBuilder, OnError},
+    mmap::{MmapCSVSource, MmapCSVSourceBuilder},
+    DataSource,
+};
+use connector_agent::writers::{dummy::U64Writer, mixed::MemoryWriter};
+use connector_agent::{ConnectorAgentError, DataType, Dispatcher, RowCount};
+use ndarray::array;
+
+#[test]
+fn mmap_batches() {
+    for &row_count in &[RowCount::Query, RowCount::Exact, RowCount::Unknown] {
+        for &(batch_size, eager) in &[(None, false), (Some(2), false), (Some(2), true)] {
+            let schema = vec![DataType::U64; 5];
+            let files = vec![
+                "./tests/data/uint_0.csv".to_string(),
+                "./tests/data/uint_1.csv".to_string(),
+            ];
+            let mut builder = MmapCSVSourceBuilder::new().with_eager(eager);
+            if let Some(batch_size) = batch_size {
+                builder = builder.with_batch_size(batch_size).unwrap();
+            }
+            let dispatcher =
+                Dispatcher::new(builder, U64Writer::new(), schema, files).with_row_count(row_count);
+
+            let dw = dispatcher.run_checked().expect("run dispatcher");
+            assert_eq!(
+                array![
+                    [0, 1, 2, 3, 4],
+                    [5, 6, 7, 8, 9],
+                    [10, 11, 12, 13, 14],
+                    [15, 16, 17, 18, 19],
+                    [20, 21, 22, 23, 24],
+                    [25, 26, 27, 28, 29],
+                    [30, 31, 32, 33, 34],
+                    [35, 36, 37, 38, 39],
+                    [40, 41, 42, 43, 44],
+                    [45, 46, 47, 48, 49],
+                    [50, 51, 52, 53, 54],
+                ],
+                dw.buffer(),
+                "{:?} {:?} {}",
+                row_count,
+                batch_size,
+                eager
+            );
+        }
+    }
+}
+
+#[test]
+fn mmap_dialect() {
+    let options = CSVSourceBuilder::new()
+        .with_skip_rows(1)
+        .with_comment(b'#')
+        .with_headers(true)
+        .with_delimiter(b';')
+        .with_quote(Some(b'\''))
+        .with_trim(true)
+        .with_flexible(true)
+        .options()
+        .clone();
+
+    let mut source = MmapCSVSource::with_options(options.clone());
+    source.run_query("./tests/data/dialect.csv").unwrap();
+    assert_eq!(
+        Some(
+            &[
+                "city".to_string(),
+                "state".to_string(),
+                "population".to_string()
+            ][..]
+        ),
+        source.headers()
+    );
+    assert_eq!((3, 3), (source.nrows, source.ncols));
+
+    let schema = vec![DataType::String, DataType::String, DataType::OptU64];
+    let files = vec!["./tests/data/dialect.csv".to_string()];
+    let builder = MmapCSVSourceBuilder::with_options(options);
+    let dw = Dispatcher::new(builder, MemoryWriter::new(), schema, files)
+        .run_checked()
+        .expect("run dispatcher");
+    assert_eq!(
+        vec!["Kenai; AK", "It's", "El Mirage"],
+        dw.column_view::<String>(0).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![Some(7610), Some(18980), None],
+        dw.column_view::<Option<u64>>(2).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn same_as_csv() {
+    let schema = vec![DataType::U64, DataType::String];
+    let files = vec!["./tests/data/quoted.csv".to_string()];
+    let options = CSVOptions {
+        has_headers: true,
+        ..CSVOptions::default()
+    };
+
+    let csv = Dispatcher::new(
+        CSVSourceBuilder::with_options(options.clone()),
+        MemoryWriter::new(),
+        schema.clone(),
+        files.clone(),
+    )
+    .run_checked()
+    .expect("run csv dispatcher");
+    let mmap = Dispatcher::new(
+        MmapCSVSourceBuilder::with_options(options.clone())
+            .with_batch_size(3)
+            .unwrap(),
+        MemoryWriter::new(),
+        schema,
+        files.clone(),
+    )
+    .with_row_count(RowCount::Exact)
+    .run_checked()
+    .expect("run mmap dispatcher");
+
+    assert_eq!(20, mmap.column_view::<u64>(0).unwrap().len());
+    assert_eq!(
+        csv.column_view::<u64>(0).unwrap(),
+        mmap.column_view::<u64>(0).unwrap()
+    );
+    assert_eq!(
+        csv.column_view::<String>(1).unwrap(),
+        mmap.column_view::<String>(1).unwrap()
+    );
+    assert_eq!(
+        "row 1\nspans, \"two\" lines",
+        mmap.column_view::<String>(1).unwrap()[1]
+    );
+
+    let mut source = MmapCSVSource::with_options(options);
+    assert_eq!(
+        20,
+        source.count_rows(&files[0], RowCount::Estimate).unwrap()
+    );
+}
+
+#[test]
+fn mmap_parse_error() {
+    let files = vec!["./tests/data/invalid.csv".to_string()];
+    let schema = vec![DataType::U64, DataType::OptF64, DataType::OptBool];
+    match Dispatcher::new(
+        MmapCSVSourceBuilder::new(),
+        MemoryWriter::new(),
+        schema,
+        files.clone(),
+    )
+    .run_checked()
+    {
+        Err(ConnectorAgentError::CSVParseError {
+            path,
+            line,
+            col,
+            text,
+            ty,
+        }) => {
+            assert_eq!("./tests/data/invalid.csv", path);
+            assert_eq!((2, 0), (line, col));
+            assert_eq!(("x", "u64"), (text.as_str(), ty));
+        }
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("invalid values are parsed"),
+    }
+
+    let options = CSVOptions {
+        on_error: OnError::Null,
+        ..CSVOptions::default()
+    };
+    let schema = vec![DataType::OptU64, DataType::OptF64, DataType::OptBool];
+    let dw = Dispatcher::new(
+        MmapCSVSourceBuilder::with_options(options),
+        MemoryWriter::new(),
+        schema,
+        files,
+    )
+    .run_checked()
+    .expect("run dispatcher");
+    assert_eq!(
+        vec![Some(1), None, Some(3)],
+        dw.column_view::<Option<u64>>(0).unwrap().to_vec()
+    );
+    assert_eq!(
+        vec![Some(true), Some(false), None],
+        dw.column_view::<Option<bool>>(2).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn mmap_compressed_and_empty() {
+    let mut source = MmapCSVSource::new();
+    assert!(source.run_query("./tests/data/uint_1.csv.gz").is_err());
+
+    source.run_query("./tests/data/empty.csv").unwrap();
+    assert_eq!((0, 0), (source.nrows, source.ncols));
+    assert_eq!(
+        0,
+        source
+            .count_rows("./tests/data/empty.csv", RowCount::Exact)
+            .unwrap()
+    );
+}
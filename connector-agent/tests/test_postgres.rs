@@ -0,0 +1,707 @@
+// The tests needing a running Postgres are ignored by default. Point `POSTGRES_URL` to it and
+// run them with e.g.
+// `POSTGRES_URL="host=localhost user=postgres" cargo test --test test_postgres -- --ignored`.
+// The fixture in `tests/data/postgres.sql` is loaded before the tests run.
+
+use arrow::array::{Array, BooleanArray, Float64Array, Int64Array, ListArray, StringArray};
+use chrono::{NaiveDate, NaiveDateTime};
+use connector_agent::{
+    data_sources::postgres::ctid_queries,
+    writers::{arrow::ArrowWriter, mixed::MemoryWriter},
+    CancellationToken, ConnectorAgentError, DataSource, DataType, Dispatcher, PartitionProgress,
+    Phase, PostgresSourceBuilder, RangeQuery, RowCount, SourceBuilder,
+};
+use ndarray::array;
+use postgres::{Client, NoTls};
+use rayon::ThreadPoolBuilder;
+use std::env;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static FIXTURE: Once = Once::new();
+
+fn postgres_url() -> String {
+    let url = env::var("POSTGRES_URL").expect("POSTGRES_URL is not set");
+    FIXTURE.call_once(|| {
+        let mut client = Client::connect(&url, NoTls).expect("connect to postgres");
+        client
+            .batch_execute(include_str!("data/postgres.sql"))
+            .expect("load fixture");
+    });
+    url
+}
+
+#[test]
+#[ignore]
+fn describe_types() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let (names, schema) = builder
+        .describe("SELECT * FROM test_types")
+        .expect("describe query");
+
+    assert_eq!(
+        vec![
+            "test_int2",
+            "test_int4",
+            "test_int8",
+            "test_float4",
+            "test_float8",
+            "test_numeric",
+            "test_text",
+            "test_varchar",
+            "test_bool",
+            "test_date",
+            "test_timestamp",
+            "test_bytea",
+        ],
+        names
+    );
+    assert_eq!(
+        vec![
+            DataType::OptI64,
+            DataType::OptI64,
+            DataType::OptI64,
+            DataType::OptF64,
+            DataType::OptF64,
+            DataType::OptF64,
+            DataType::OptString,
+            DataType::OptString,
+            DataType::OptBool,
+            DataType::OptDate,
+            DataType::OptDateTime,
+            DataType::OptBytes,
+        ],
+        schema
+    );
+}
+
+#[test]
+#[ignore]
+fn unsupported_type() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    match builder.describe("SELECT '{}'::json AS test_json") {
+        Err(ConnectorAgentError::UnsupportedPostgresType(name, oid)) => {
+            assert_eq!("json", name);
+            assert_eq!(114, oid);
+        }
+        _ => panic!("json should not be supported"),
+    }
+}
+
+#[test]
+#[ignore]
+fn read_types() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 2).expect("create builder");
+    let queries = vec![
+        "SELECT * FROM test_types WHERE test_int2 IS NOT NULL".to_string(),
+        "SELECT * FROM test_types WHERE test_int2 IS NULL".to_string(),
+    ];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        array![Some(1), None],
+        dw.column_view::<Option<i64>>(0).unwrap()
+    );
+    assert_eq!(
+        array![Some(-2), None],
+        dw.column_view::<Option<i64>>(1).unwrap()
+    );
+    assert_eq!(
+        array![Some(3000000000), None],
+        dw.column_view::<Option<i64>>(2).unwrap()
+    );
+    assert_eq!(
+        array![Some(1.5), None],
+        dw.column_view::<Option<f64>>(3).unwrap()
+    );
+    assert_eq!(
+        array![Some(-2.25), None],
+        dw.column_view::<Option<f64>>(4).unwrap()
+    );
+    assert_eq!(
+        array![Some(12345.678), None],
+        dw.column_view::<Option<f64>>(5).unwrap()
+    );
+    assert_eq!(
+        array![Some("a".to_string()), None],
+        dw.column_view::<Option<String>>(6).unwrap()
+    );
+    assert_eq!(
+        array![Some("b".to_string()), None],
+        dw.column_view::<Option<String>>(7).unwrap()
+    );
+    assert_eq!(
+        array![Some(true), None],
+        dw.column_view::<Option<bool>>(8).unwrap()
+    );
+    assert_eq!(
+        array![Some(NaiveDate::from_ymd(2021, 1, 1)), None],
+        dw.column_view::<Option<NaiveDate>>(9).unwrap()
+    );
+    assert_eq!(
+        array![
+            Some(
+                NaiveDateTime::parse_from_str("2021-01-01 12:34:56.789", "%Y-%m-%d %H:%M:%S%.f")
+                    .unwrap()
+            ),
+            None
+        ],
+        dw.column_view::<Option<NaiveDateTime>>(10).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![1u8, 2]), None],
+        dw.column_view::<Option<Vec<u8>>>(11).unwrap()
+    );
+}
+
+#[test]
+#[ignore]
+fn describe_custom_types() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let (_, schema) = builder
+        .describe("SELECT * FROM test_custom_types")
+        .expect("describe query");
+
+    assert_eq!(
+        vec![
+            DataType::OptI64,
+            DataType::OptString,
+            DataType::OptI64List,
+            DataType::OptF64List,
+            DataType::OptBoolList,
+            DataType::OptStringList,
+            DataType::OptI64List,
+        ],
+        schema
+    );
+}
+
+/// The rows of the custom types in a fixed order: the lists of the second one are empty and
+/// those of the last one null.
+const CUSTOM_TYPES: &str = "SELECT * FROM test_custom_types ORDER BY test_domain";
+
+#[test]
+#[ignore]
+fn read_custom_types() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let queries = vec![CUSTOM_TYPES.to_string()];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        array![Some(1), Some(2), None],
+        dw.column_view::<Option<i64>>(0).unwrap()
+    );
+    assert_eq!(
+        array![Some("happy".to_string()), Some("sad".to_string()), None],
+        dw.column_view::<Option<String>>(1).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![Some(1), None, Some(3)]), Some(vec![]), None],
+        dw.column_view::<Option<Vec<Option<i64>>>>(2).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![Some(0.5)]), Some(vec![]), None],
+        dw.column_view::<Option<Vec<Option<f64>>>>(3).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![Some(true), Some(false)]), Some(vec![]), None],
+        dw.column_view::<Option<Vec<Option<bool>>>>(4).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![Some("a".to_string()), None]), Some(vec![]), None],
+        dw.column_view::<Option<Vec<Option<String>>>>(5).unwrap()
+    );
+    assert_eq!(
+        array![Some(vec![Some(2)]), Some(vec![]), None],
+        dw.column_view::<Option<Vec<Option<i64>>>>(6).unwrap()
+    );
+}
+
+#[test]
+#[ignore]
+fn read_custom_types_arrow() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let queries = vec![CUSTOM_TYPES.to_string()];
+    let (names, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, ArrowWriter::new(), schema, queries);
+    let batches = dispatcher
+        .run_checked()
+        .expect("run dispatcher")
+        .finish(names);
+    assert_eq!(1, batches.len());
+    let rb = &batches[0];
+    assert_eq!(3, rb.num_rows());
+
+    // the list columns are built item by item, nulls included
+    let lists: Vec<&ListArray> = (2..7)
+        .map(|col| {
+            rb.column(col)
+                .as_any()
+                .downcast_ref::<ListArray>()
+                .expect("list column")
+        })
+        .collect();
+    for list in &lists {
+        assert!(list.is_valid(1));
+        assert_eq!(0, list.value_length(1));
+        assert!(list.is_null(2));
+    }
+    let first = |col: usize| lists[col - 2].value(0);
+    assert!(first(2)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .eq(&Int64Array::from(vec![Some(1), None, Some(3)])));
+    assert!(first(3)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap()
+        .eq(&Float64Array::from(vec![0.5])));
+    assert!(first(4)
+        .as_any()
+        .downcast_ref::<BooleanArray>()
+        .unwrap()
+        .eq(&BooleanArray::from(vec![true, false])));
+    assert!(first(5)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap()
+        .eq(&StringArray::from(vec![Some("a"), None])));
+    assert!(first(6)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .eq(&Int64Array::from(vec![2])));
+}
+
+#[test]
+#[ignore]
+fn read_with_cursor() {
+    let url = postgres_url();
+
+    let mut builder = PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_cursor(3)
+        .expect("use cursor");
+    let mut source = builder.build();
+    source
+        .run_query("SELECT generate_series(1, 10);")
+        .expect("run query");
+
+    let mut batches = vec![];
+    let mut values = vec![];
+    let mut n = source.nrows();
+    while n > 0 {
+        batches.push(n);
+        for _ in 0..n {
+            values.push(source.read::<i64>().expect("read value"));
+        }
+        n = source.fetch_next().expect("fetch next batch");
+    }
+
+    assert_eq!(vec![3, 3, 3, 1], batches);
+    assert_eq!((1..=10).collect::<Vec<i64>>(), values);
+
+    match PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_cursor(0)
+    {
+        Err(ConnectorAgentError::ZeroBatchSize) => {}
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("a cursor fetches no rows at a time"),
+    }
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_cursor() {
+    let url = postgres_url();
+
+    // the rows are not counted up front with a cursor, and the writers grow as they arrive
+    let builder = PostgresSourceBuilder::new(&url, 2)
+        .expect("create builder")
+        .with_cursor(10)
+        .expect("use cursor");
+    let queries = vec![
+        "SELECT test_int8 FROM test_types ORDER BY test_int8".to_string(),
+        "SELECT test_int8 FROM test_types WHERE test_int8 IS NOT NULL".to_string(),
+    ];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        array![Some(3000000000), None, Some(3000000000)],
+        dw.column_view::<Option<i64>>(0).unwrap()
+    );
+
+    // partitions spanning several batches
+    let builder = PostgresSourceBuilder::new(&url, 2)
+        .expect("create builder")
+        .with_cursor(3)
+        .expect("use cursor");
+    let queries = vec![
+        "SELECT generate_series(1, 10)".to_string(),
+        "SELECT generate_series(11, 17)".to_string(),
+    ];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        (1..=17).map(Some).collect::<Vec<Option<i64>>>(),
+        dw.column_view::<Option<i64>>(0).unwrap().to_vec()
+    );
+
+    // more queries than connections, which the cursors of the first queries would hold on to
+    // if all of them were run before allocating
+    let builder = PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_cursor(3)
+        .expect("use cursor");
+    let queries: Vec<String> = (0..3)
+        .map(|i| format!("SELECT generate_series({}, {})", 10 * i + 1, 10 * i + 10))
+        .collect();
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+        .with_row_count(RowCount::Query)
+        .with_timeout(Duration::from_secs(10));
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        (1..=30).map(Some).collect::<Vec<Option<i64>>>(),
+        dw.column_view::<Option<i64>>(0).unwrap().to_vec()
+    );
+}
+
+#[test]
+fn ctid_ranges() {
+    assert_eq!(
+        vec![
+            "SELECT * FROM t WHERE ctid < '(3,0)'",
+            "SELECT * FROM t WHERE ctid >= '(3,0)' AND ctid < '(6,0)'",
+            "SELECT * FROM t WHERE ctid >= '(6,0)'",
+        ],
+        ctid_queries("t", 10, 3)
+    );
+    assert_eq!(
+        vec![
+            "SELECT * FROM t WHERE ctid < '(1,0)'",
+            "SELECT * FROM t WHERE ctid >= '(1,0)'",
+        ],
+        ctid_queries("t", 2, 4)
+    );
+    assert_eq!(vec!["SELECT * FROM t"], ctid_queries("t", 0, 4));
+}
+
+#[test]
+#[ignore]
+fn read_ctid_partitions() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 4).expect("create builder");
+    let queries = builder
+        .ctid_partitions("test_ctid", 4)
+        .expect("partition table");
+    assert_eq!(4, queries.len());
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    let mut values: Vec<i64> = dw
+        .column_view::<Option<i64>>(0)
+        .unwrap()
+        .iter()
+        .map(|v| v.unwrap())
+        .collect();
+    values.sort();
+    assert_eq!((1..=10000).collect::<Vec<i64>>(), values);
+}
+
+#[test]
+#[ignore]
+fn count_rows() {
+    let url = postgres_url();
+
+    let mut builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let mut source = builder.build();
+    let query = "SELECT * FROM test_types WHERE test_int2 IS NULL;";
+    assert_eq!(
+        1,
+        source
+            .count_rows(query, RowCount::Exact)
+            .expect("count rows")
+    );
+    // the planner does not know how many rows match, just that there are some
+    assert!(
+        source
+            .count_rows(query, RowCount::Estimate)
+            .expect("estimate rows")
+            > 0
+    );
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_cursor_and_exact_row_count() {
+    let url = postgres_url();
+
+    // with exact counts, a writer of fixed size can take results fetched in many batches
+    let builder = PostgresSourceBuilder::new(&url, 2)
+        .expect("create builder")
+        .with_cursor(3)
+        .expect("use cursor");
+    let queries = vec![
+        "SELECT generate_series(1, 10)".to_string(),
+        "SELECT generate_series(11, 12)".to_string(),
+    ];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+        .with_row_count(RowCount::Exact);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    assert_eq!(
+        (1..=12).map(Some).collect::<Vec<Option<i64>>>(),
+        dw.column_view::<Option<i64>>(0).unwrap().to_vec()
+    );
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_cursor_and_estimated_row_count() {
+    let url = postgres_url();
+
+    // the estimates are off, so the partition writers have to grow or shrink
+    let builder = PostgresSourceBuilder::new(&url, 2)
+        .expect("create builder")
+        .with_cursor(3)
+        .expect("use cursor");
+    let queries = vec![
+        "SELECT test_int FROM test_ctid WHERE test_int <= 10".to_string(),
+        "SELECT test_int FROM test_ctid WHERE test_int > 9990".to_string(),
+    ];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+        .with_row_count(RowCount::Estimate);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    let mut values: Vec<i64> = dw
+        .column_view::<Option<i64>>(0)
+        .unwrap()
+        .iter()
+        .map(|v| v.unwrap())
+        .collect();
+    values.sort();
+    assert_eq!((1..=10).chain(9991..=10000).collect::<Vec<i64>>(), values);
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_progress() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_cursor(4)
+        .expect("use cursor");
+    let queries = vec!["SELECT test_int FROM test_ctid WHERE test_int <= 10".to_string()];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let reports: Arc<Mutex<Vec<PartitionProgress>>> = Arc::new(Mutex::new(vec![]));
+    let collected = Arc::clone(&reports);
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+        .with_row_count(RowCount::Unknown)
+        .with_progress(move |p: &PartitionProgress| collected.lock().unwrap().push(*p));
+    dispatcher.run_checked().expect("run dispatcher");
+
+    // one report per batch fetched through the cursor, with the bytes of the int4 values read
+    let reports = reports.lock().unwrap();
+    let transfers: Vec<_> = reports
+        .iter()
+        .filter(|p| p.phase == Phase::Transfer)
+        .map(|p| (p.rows, p.total, p.bytes))
+        .collect();
+    assert_eq!(
+        vec![(4, None, 16), (8, None, 32), (10, None, 40)],
+        transfers
+    );
+}
+
+const SLOW_QUERY: &str = "SELECT 1::int8 AS test_int FROM pg_sleep(30)";
+
+#[test]
+#[ignore]
+fn query_timeout() {
+    let url = postgres_url();
+
+    let timeout = Duration::from_millis(200);
+    let mut builder = PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_query_timeout(timeout);
+    let mut source = builder.build();
+
+    let start = Instant::now();
+    match source.run_query(SLOW_QUERY) {
+        Err(ConnectorAgentError::Timeout(t)) => assert_eq!(timeout, t),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the query does not time out"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // every fetch from a cursor is timed on its own, by the same timers
+    let mut builder = PostgresSourceBuilder::new(&url, 1)
+        .expect("create builder")
+        .with_cursor(2)
+        .expect("use cursor")
+        .with_query_timeout(timeout);
+    let mut source = builder.build();
+    source
+        .run_query("SELECT generate_series(1, 10)::int8")
+        .expect("run query");
+    let (mut n, mut fetched) = (source.nrows(), source.nrows());
+    while fetched > 0 {
+        // longer than the timeout, which would fail the next fetch if the last timer fired
+        std::thread::sleep(timeout + Duration::from_millis(50));
+        fetched = source.fetch_next().expect("fetch rows");
+        n += fetched;
+    }
+    assert_eq!(10, n);
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_timeout() {
+    let url = postgres_url();
+
+    let timeout = Duration::from_millis(200);
+    let builder = PostgresSourceBuilder::new(&url, 2).expect("create builder");
+    let queries = vec![SLOW_QUERY.to_string(), SLOW_QUERY.to_string()];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let start = Instant::now();
+    let dispatcher =
+        Dispatcher::new(builder, MemoryWriter::new(), schema, queries).with_timeout(timeout);
+    match dispatcher.run_checked() {
+        Err(ConnectorAgentError::Timeout(t)) => assert_eq!(timeout, t),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the run does not time out"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+#[ignore]
+fn dispatch_cancelled() {
+    let url = postgres_url();
+
+    let builder = PostgresSourceBuilder::new(&url, 1).expect("create builder");
+    let queries = vec![SLOW_QUERY.to_string()];
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        cancel.cancel();
+    });
+
+    let start = Instant::now();
+    let dispatcher =
+        Dispatcher::new(builder, MemoryWriter::new(), schema, queries).with_cancellation(token);
+    match dispatcher.run_checked() {
+        Err(ConnectorAgentError::Cancelled) => {}
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the run is not cancelled"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+    canceller.join().unwrap();
+}
+
+#[test]
+#[ignore]
+fn dispatch_with_max_concurrency() {
+    let url = postgres_url();
+
+    // more partitions than connections, on enough threads to run all of them at once
+    let builder = PostgresSourceBuilder::new(&url, 2).expect("create builder");
+    let queries: Vec<String> = (0..6)
+        .map(|i| format!("SELECT {}::int8 AS test_int FROM pg_sleep(0.2)", i))
+        .collect();
+    let (_, schema) = builder.describe(&queries[0]).expect("describe query");
+    let pool = ThreadPoolBuilder::new().num_threads(6).build().unwrap();
+
+    let start = Instant::now();
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+        .with_thread_pool(Arc::new(pool))
+        .with_max_concurrency(2)
+        .expect("limit concurrency");
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    // no more than two queries sleep at the same time
+    assert!(start.elapsed() >= Duration::from_millis(600));
+    let mut values: Vec<i64> = dw
+        .column_view::<Option<i64>>(0)
+        .unwrap()
+        .iter()
+        .map(|v| v.unwrap())
+        .collect();
+    values.sort();
+    assert_eq!(vec![0, 1, 2, 3, 4, 5], values);
+}
+
+#[test]
+#[ignore]
+fn dispatch_range_split() {
+    let url = postgres_url();
+
+    let query = "SELECT i::int8 AS test_int FROM generate_series(0, 999) AS i";
+    let builder = PostgresSourceBuilder::new(&url, 4).expect("create builder");
+    let (_, schema) = builder.describe(query).expect("describe query");
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let parts = vec![
+        RangeQuery::new(query, "test_int", 0..100),
+        RangeQuery::new(query, "test_int", 100..1000)
+            .with_min_len(100)
+            .unwrap(),
+    ];
+
+    let dispatcher = Dispatcher::new_split(builder, MemoryWriter::new(), schema, parts)
+        .with_row_count(RowCount::Exact)
+        .with_thread_pool(Arc::new(pool));
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+
+    let mut values: Vec<i64> = dw
+        .column_view::<Option<i64>>(0)
+        .unwrap()
+        .iter()
+        .map(|v| v.unwrap())
+        .collect();
+    values.sort();
+    assert_eq!((0..1000).collect::<Vec<i64>>(), values);
+}
@@ -0,0 +1,124 @@
+use connector_agent::data_sources::{
+    csv::{CSVOptions, OnError},
+    DataSource, Produce, SourceBuilder,
+};
+use connector_agent::writers::{dummy::U64Writer, mixed::MemoryWriter};
+use connector_agent::{DataType, Dispatcher, ReaderSourceBuilder, RowCount};
+use ndarray::array;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor};
+use std::sync::Arc;
+
+fn buffers() -> Arc<HashMap<String, Vec<u8>>> {
+    let mut buffers = HashMap::new();
+    buffers.insert("a".to_string(), b"id,name\n1,x\n2,y\n".to_vec());
+    buffers.insert("b".to_string(), b"id,name\n3,z\n".to_vec());
+    Arc::new(buffers)
+}
+
+fn open(
+    buffers: Arc<HashMap<String, Vec<u8>>>,
+) -> impl Fn(&str) -> io::Result<Cursor<Vec<u8>>> + Send + Sync {
+    move |query| match buffers.get(query) {
+        Some(buffer) => Ok(Cursor::new(buffer.clone())),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, query.to_string())),
+    }
+}
+
+#[test]
+fn read_buffers() {
+    let options = CSVOptions {
+        has_headers: true,
+        filename_column: Some("buffer".to_string()),
+        ..CSVOptions::default()
+    };
+    for &row_count in &[RowCount::Query, RowCount::Exact, RowCount::Estimate] {
+        let builder = ReaderSourceBuilder::new(open(buffers()))
+            .with_options(options.clone())
+            .with_batch_size(1)
+            .unwrap();
+        let schema = vec![DataType::U64, DataType::String, DataType::String];
+        let queries = vec!["a".to_string(), "b".to_string()];
+        let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, queries)
+            .with_row_count(row_count);
+        let dw = dispatcher.run_checked().expect("run dispatcher");
+        assert_eq!(vec![1, 2, 3], dw.column_view::<u64>(0).unwrap().to_vec());
+        assert_eq!(
+            vec!["x", "y", "z"],
+            dw.column_view::<String>(1).unwrap().to_vec()
+        );
+        assert_eq!(
+            vec!["a", "a", "b"],
+            dw.column_view::<String>(2).unwrap().to_vec()
+        );
+    }
+}
+
+#[test]
+fn read_compressed() {
+    let gzip = fs::read("./tests/data/uint_1.csv.gz").unwrap();
+    let builder = ReaderSourceBuilder::new(move |_: &str| Ok(Cursor::new(gzip.clone())));
+    let dispatcher = Dispatcher::new(
+        builder,
+        U64Writer::new(),
+        vec![DataType::U64; 5],
+        vec!["body".to_string()],
+    );
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(
+        array![
+            [20, 21, 22, 23, 24],
+            [25, 26, 27, 28, 29],
+            [30, 31, 32, 33, 34],
+            [35, 36, 37, 38, 39],
+            [40, 41, 42, 43, 44],
+            [45, 46, 47, 48, 49],
+            [50, 51, 52, 53, 54],
+        ],
+        dw.buffer()
+    );
+}
+
+#[test]
+fn reader_errors() {
+    let mut builder = ReaderSourceBuilder::new(open(buffers())).with_options(CSVOptions {
+        has_headers: true,
+        on_error: OnError::Fail,
+        ..CSVOptions::default()
+    });
+
+    let mut source = builder.build();
+    assert!(source.run_query("c").is_err());
+
+    source.run_query("a").unwrap();
+    assert_eq!(
+        Some(&["id".to_string(), "name".to_string()][..]),
+        source.headers()
+    );
+    assert_eq!(2, source.count_rows("a", RowCount::Estimate).unwrap());
+    assert!(Produce::<u64>::produce(&mut source).is_ok());
+    assert!(Produce::<u64>::produce(&mut source).is_err());
+}
+
+#[test]
+fn reader_skip() {
+    let rejects = std::env::temp_dir().join(format!("reader_rejects_{}.csv", std::process::id()));
+    let mut buffers = HashMap::new();
+    buffers.insert("a".to_string(), b"1,x\ny,2\n3,z\n".to_vec());
+    let schema = vec![DataType::U64, DataType::String];
+    let builder = ReaderSourceBuilder::new(open(Arc::new(buffers)))
+        .with_options(CSVOptions {
+            on_error: OnError::Skip,
+            ..CSVOptions::default()
+        })
+        .with_schema(schema.clone())
+        .with_reject_file(rejects.to_str().unwrap());
+    let dispatcher = Dispatcher::new(builder, MemoryWriter::new(), schema, vec!["a".to_string()]);
+    let dw = dispatcher.run_checked().expect("run dispatcher");
+    assert_eq!(vec![1, 3], dw.column_view::<u64>(0).unwrap().to_vec());
+
+    let rejected = fs::read_to_string(&rejects).expect("read reject file");
+    fs::remove_file(&rejects).unwrap();
+    assert_eq!("a,2,y,2\n", rejected);
+}
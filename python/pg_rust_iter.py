@@ -0,0 +1,26 @@
+import sys
+import connector_agent
+import pyarrow as pa
+import time
+from keys import *
+
+
+if __name__ == '__main__':
+    t_num = int(sys.argv[1])
+    sqls = get_sqls(t_num)
+    print(f"numer of threads: {t_num}\nsqls: {sqls}")
+
+    then = time.time()
+    nrows = 0
+    batches = []
+    for batch in connector_agent.iter_sql(sqls, batch_size=65536):
+        nrows += batch.num_rows
+        batches.append(batch)
+    print(f"finish iter_sql: {nrows} rows in {len(batches)} batches", time.time() - then)
+
+    tb = pa.Table.from_batches(batches)
+    print("finish concat:", time.time() - then)
+
+    df = tb.to_pandas()
+    print("finish to_pandas:", time.time() - then)
+    print(df)
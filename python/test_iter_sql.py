@@ -0,0 +1,55 @@
+"""Tests of `iter_sql`, run with `just test-python` against the database of `pg.rs`."""
+import math
+import os
+import subprocess
+import sys
+
+import connector_agent
+
+SQLS = ["select i, i::text from generate_series(1, 1000000) as i"] * 4
+
+# the iterator is dropped while the partitions report progress, both by leaving the loop early
+# and by losing the last reference to it
+BREAK_EARLY = f"""
+import connector_agent
+
+reports = []
+for batch in connector_agent.iter_sql({SQLS!r}, batch_size=1024, progress=lambda *p: reports.append(p)):
+    break
+
+batches = connector_agent.iter_sql({SQLS!r}, batch_size=1024, progress=lambda *p: reports.append(p))
+next(batches)
+del batches
+"""
+
+
+def test_break_with_progress():
+    # a deadlock would hang the interpreter, so the loops run in a child process
+    subprocess.run(
+        [sys.executable, "-c", BREAK_EARLY],
+        cwd=os.path.dirname(os.path.abspath(__file__)),
+        check=True,
+        timeout=60,
+    )
+
+
+def test_invalid_options():
+    # raised before connecting to the database
+    invalid = [
+        {"batch_size": 0},
+        {"concurrency": 0},
+        {"timeout": -1.0},
+        {"timeout": math.nan},
+        {"query_timeout": math.inf},
+    ]
+    for kwargs in invalid:
+        try:
+            connector_agent.iter_sql(SQLS, **kwargs)
+        except ValueError:
+            continue
+        raise AssertionError(f"{kwargs} is accepted")
+
+
+if __name__ == "__main__":
+    test_break_with_progress()
+    test_invalid_options()